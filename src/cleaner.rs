@@ -29,7 +29,7 @@ use svgdom::{Document, ParseOptions, WriteOptions, WriteBuffer, ElementId};
 
 use svgdom_utils;
 
-use options::Options;
+use options::{Options, DuplElements};
 use task::*;
 use error;
 
@@ -66,6 +66,12 @@ pub fn clean_doc(doc: &Document, options: &Options, opt: &WriteOptions)
     // Do not remove any attributes before this methods
     // since they uses them.
 
+    if options.resolve_units {
+        // Resolve units before processing, because most of the algorithms
+        // can process only lengths in user units.
+        resolve_units(doc);
+    }
+
     if options.remove_title {
         remove_element(doc, ElementId::Title);
     }
@@ -86,6 +92,11 @@ pub fn clean_doc(doc: &Document, options: &Options, opt: &WriteOptions)
         remove_invalid_stops(doc);
     }
 
+    if options.optimize_filters {
+        // Optimize filters before processing to simplify duplicates detecting.
+        optimize_filters(doc, opt);
+    }
+
     if options.apply_transform_to_gradients {
         // Apply transform to gradients before processing to simplify duplicates
         // detecting and merging.
@@ -104,6 +115,10 @@ pub fn clean_doc(doc: &Document, options: &Options, opt: &WriteOptions)
         remove_dupl_fe_gaussian_blur(doc);
     }
 
+    if options.remove_dupl_elements.is_any() {
+        remove_dupl_elements(doc, &options.remove_dupl_elements);
+    }
+
     if options.merge_gradients {
         merge_gradients(doc);
     }
@@ -113,9 +128,30 @@ pub fn clean_doc(doc: &Document, options: &Options, opt: &WriteOptions)
         apply_transforms::apply_transform_to_gradients(doc);
     }
 
+    if options.fit_viewbox {
+        // Apply before 'apply_transform_to_shapes', so the content translation
+        // can be applied to shapes.
+        fit_viewbox(doc, options.fit_viewbox_padding);
+    }
+
+    if options.normalize_size > 0.0 {
+        // Run after 'fit_viewbox', because the 'viewBox' is used as a source rect.
+        normalize_size(doc, options.normalize_size, options.preserve_stroke_width);
+    }
+
+    if options.rebase_coordinates {
+        let precision = if options.rebase_coordinates_scale {
+            Some(opt.paths.coordinates_precision)
+        } else {
+            None
+        };
+
+        rebase_coordinates(doc, precision);
+    }
+
     if options.apply_transform_to_shapes {
         // Apply before 'convert_shapes_to_paths'.
-        apply_transforms::apply_transform_to_shapes(doc);
+        apply_transforms::apply_transform_to_shapes(doc, options);
     }
 
     if options.convert_shapes {
@@ -126,11 +162,29 @@ pub fn clean_doc(doc: &Document, options: &Options, opt: &WriteOptions)
     //       segments from the path which makes it invisible.
     if options.paths_to_relative {
         // we only process path's segments if 'PathsToRelative' is enabled
-        paths::process_paths(doc, options);
+        paths::process_paths(doc, options, opt);
     }
 
     if options.remove_invisible_elements {
-        remove_invisible_elements(doc);
+        remove_invisible_elements(doc, options);
+
+        // Masks can be converted into clip paths, which can be duplicates now.
+        let dupl = &options.remove_dupl_elements;
+        if dupl.mask || dupl.clip_path {
+            remove_dupl_elements(doc, &DuplElements {
+                mask: dupl.mask,
+                clip_path: dupl.clip_path,
+                ..DuplElements::default()
+            });
+        }
+    }
+
+    if options.remove_offscreen_elements {
+        remove_offscreen_elements(doc);
+    }
+
+    if options.remove_occluded_elements {
+        remove_occluded_elements(doc);
     }
 
     if options.regroup_gradient_stops {
@@ -138,7 +192,9 @@ pub fn clean_doc(doc: &Document, options: &Options, opt: &WriteOptions)
     }
 
     if options.ungroup_groups {
-        ungroup_groups(doc);
+        // Nested 'svg' elements are converted into groups, which can be ungrouped afterward.
+        ungroup_nested_svg(doc);
+        ungroup_groups(doc, options, opt);
     }
 
     if options.resolve_use {
@@ -160,13 +216,19 @@ pub fn clean_doc(doc: &Document, options: &Options, opt: &WriteOptions)
     }
 
     if options.remove_gradient_attributes {
-        remove_gradient_attributes(doc);
+        remove_gradient_attributes(doc, opt);
     }
 
     if options.remove_unused_coordinates {
         remove_unused_coordinates(doc);
     }
 
+    // Run only after attributes processed, because
+    // elements with default/unneeded attributes are harder to compare.
+    if options.replace_dupl_with_use {
+        replace_dupl_with_use(doc, opt);
+    }
+
     // Run only after attributes processed, because
     // there is no point in grouping default/unneeded attributes.
     if options.group_by_style {
@@ -26,7 +26,7 @@ use clap::{Arg, App, ArgMatches};
 
 use svgdom::{ParseOptions, WriteOptions};
 
-use options::Options;
+use options::{Options, DuplElements};
 
 #[derive(Clone,Copy)]
 pub enum Key {
@@ -41,6 +41,7 @@ pub enum Key {
     RemoveDuplLinearGradients,
     RemoveDuplRadialGradients,
     RemoveDuplFeGaussianBlur,
+    RemoveDuplElements,
     UngroupGroups,
     UngroupDefs,
     GroupByStyle,
@@ -48,7 +49,14 @@ pub enum Key {
     RegroupGradientStops,
     RemoveInvalidStops,
     RemoveInvisibleElements,
+    RemoveHiddenElements,
+    RemoveUnpaintedElements,
+    RemoveEmptyShapes,
     ResolveUse,
+    ReplaceDuplWithUse,
+    OptimizeFilters,
+    RemoveOffscreenElements,
+    RemoveOccludedElements,
 
     RemoveVersion,
     RemoveNonsvgAttributes,
@@ -64,6 +72,13 @@ pub enum Key {
     ApplyTransformToGradients,
     ApplyTransformToShapes,
     RemoveUnresolvedClasses,
+    FitViewbox,
+    FitViewboxPadding,
+    NormalizeSize,
+    PreserveStrokeWidth,
+    ResolveUnits,
+    RebaseCoordinates,
+    RebaseCoordinatesScale,
 
     PathsToRelative,
     RemoveUnusedSegments,
@@ -105,6 +120,7 @@ pub static KEYS: &'static KeysData<'static> = &KeysData(&[
     "remove-dupl-lineargradient",
     "remove-dupl-radialgradient",
     "remove-dupl-fegaussianblur",
+    "remove-dupl-elements",
     "ungroup-groups",
     "ungroup-defs",
     "group-by-style",
@@ -112,7 +128,14 @@ pub static KEYS: &'static KeysData<'static> = &KeysData(&[
     "regroup-gradient-stops",
     "remove-invalid-stops",
     "remove-invisible-elements",
+    "remove-hidden-elements",
+    "remove-unpainted-elements",
+    "remove-empty-shapes",
     "resolve-use",
+    "replace-dupl-with-use",
+    "optimize-filters",
+    "remove-offscreen-elements",
+    "remove-occluded-elements",
 
     "remove-version",
     "remove-nonsvg-attributes",
@@ -128,6 +151,13 @@ pub static KEYS: &'static KeysData<'static> = &KeysData(&[
     "apply-transform-to-gradients",
     "apply-transform-to-shapes",
     "remove-unresolved-classes",
+    "fit-viewbox",
+    "fit-viewbox-padding",
+    "normalize-size",
+    "preserve-stroke-width",
+    "resolve-units",
+    "rebase-coordinates",
+    "rebase-coordinates-scale",
 
     "paths-to-relative",
     "remove-unused-segments",
@@ -147,6 +177,9 @@ pub static KEYS: &'static KeysData<'static> = &KeysData(&[
     "quiet",
 ]);
 
+static DUPL_ELEMENTS_DEFAULT: &'static str =
+    "linearGradient,radialGradient,pattern,clipPath,mask,marker,symbol,filter";
+
 macro_rules! gen_flag {
     ($key:expr, $flag:expr) => (
         Arg::with_name(KEYS[$key])
@@ -187,6 +220,11 @@ pub fn prepare_app<'a, 'b>() -> App<'a, 'b> {
         .arg(gen_flag!(Key::RemoveDuplLinearGradients, "true"))
         .arg(gen_flag!(Key::RemoveDuplRadialGradients, "true"))
         .arg(gen_flag!(Key::RemoveDuplFeGaussianBlur, "true"))
+        .arg(Arg::with_name(KEYS[Key::RemoveDuplElements])
+            .long(KEYS[Key::RemoveDuplElements])
+            .value_name("LIST")
+            .validator(is_dupl_elements)
+            .default_value(DUPL_ELEMENTS_DEFAULT))
         .arg(gen_flag!(Key::UngroupGroups, "true"))
         .arg(gen_flag!(Key::UngroupDefs, "true"))
         .arg(gen_flag!(Key::GroupByStyle, "true"))
@@ -194,7 +232,14 @@ pub fn prepare_app<'a, 'b>() -> App<'a, 'b> {
         .arg(gen_flag!(Key::RegroupGradientStops, "true"))
         .arg(gen_flag!(Key::RemoveInvalidStops, "true"))
         .arg(gen_flag!(Key::RemoveInvisibleElements, "true"))
+        .arg(gen_flag!(Key::RemoveHiddenElements, "true"))
+        .arg(gen_flag!(Key::RemoveUnpaintedElements, "true"))
+        .arg(gen_flag!(Key::RemoveEmptyShapes, "true"))
         .arg(gen_flag!(Key::ResolveUse, "true"))
+        .arg(gen_flag!(Key::ReplaceDuplWithUse, "false"))
+        .arg(gen_flag!(Key::OptimizeFilters, "true"))
+        .arg(gen_flag!(Key::RemoveOffscreenElements, "false"))
+        .arg(gen_flag!(Key::RemoveOccludedElements, "false"))
 
         // attributes
         .arg(gen_flag!(Key::RemoveVersion, "true"))
@@ -211,6 +256,21 @@ pub fn prepare_app<'a, 'b>() -> App<'a, 'b> {
         .arg(gen_flag!(Key::ApplyTransformToGradients, "true"))
         .arg(gen_flag!(Key::ApplyTransformToShapes, "true"))
         .arg(gen_flag!(Key::RemoveUnresolvedClasses, "true"))
+        .arg(gen_flag!(Key::FitViewbox, "false"))
+        .arg(Arg::with_name(KEYS[Key::FitViewboxPadding])
+            .long(KEYS[Key::FitViewboxPadding])
+            .value_name("VALUE")
+            .validator(is_non_negative_number)
+            .default_value("0"))
+        .arg(Arg::with_name(KEYS[Key::NormalizeSize])
+            .long(KEYS[Key::NormalizeSize])
+            .value_name("VALUE")
+            .validator(is_non_negative_number)
+            .default_value("0"))
+        .arg(gen_flag!(Key::PreserveStrokeWidth, "false"))
+        .arg(gen_flag!(Key::ResolveUnits, "true"))
+        .arg(gen_flag!(Key::RebaseCoordinates, "false"))
+        .arg(gen_flag!(Key::RebaseCoordinatesScale, "false"))
 
         // paths
         .arg(gen_flag!(Key::PathsToRelative, "true"))
@@ -275,6 +335,64 @@ fn is_precision(val: String) -> Result<(), String> {
     }
 }
 
+fn is_non_negative_number(val: String) -> Result<(), String> {
+    let n = match val.parse::<f64>() {
+        Ok(v) => v,
+        Err(e) => return Err(format!("{}", e)),
+    };
+
+    if n >= 0.0 && n.is_finite() {
+        Ok(())
+    } else {
+        Err(String::from("Invalid value. The number must be non-negative."))
+    }
+}
+
+fn is_dupl_elements(val: String) -> Result<(), String> {
+    if val == "none" {
+        return Ok(());
+    }
+
+    let mut elements = DuplElements::default();
+    for name in val.split(',') {
+        if !set_dupl_element(&mut elements, name) {
+            return Err(format!("Unsupported element: '{}'.", name));
+        }
+    }
+
+    Ok(())
+}
+
+fn set_dupl_element(elements: &mut DuplElements, name: &str) -> bool {
+    match name {
+        "linearGradient" => elements.linear_gradient = true,
+        "radialGradient" => elements.radial_gradient = true,
+        "pattern" => elements.pattern = true,
+        "clipPath" => elements.clip_path = true,
+        "mask" => elements.mask = true,
+        "marker" => elements.marker = true,
+        "symbol" => elements.symbol = true,
+        "filter" => elements.filter = true,
+        _ => return false,
+    }
+
+    true
+}
+
+fn get_dupl_elements(args: &ArgMatches) -> DuplElements {
+    let mut elements = DuplElements::default();
+
+    // unwrap is safe, because the value has a default value
+    let val = args.value_of(KEYS[Key::RemoveDuplElements]).unwrap();
+    if val != "none" {
+        for name in val.split(',') {
+            set_dupl_element(&mut elements, name);
+        }
+    }
+
+    elements
+}
+
 fn is_flag(val: String) -> Result<(), String> {
     match val.as_ref() {
         "true" | "false" => Ok(()),
@@ -312,6 +430,29 @@ pub fn check_values(args: &ArgMatches) -> bool {
         return false;
     }
 
+    if !check_value(args, Key::RemoveInvisibleElements, Key::RemoveHiddenElements) {
+        return false;
+    }
+
+    if !check_value(args, Key::RemoveInvisibleElements, Key::RemoveUnpaintedElements) {
+        return false;
+    }
+
+    if !check_value(args, Key::RemoveInvisibleElements, Key::RemoveEmptyShapes) {
+        return false;
+    }
+
+    if !check_value(args, Key::RebaseCoordinates, Key::RebaseCoordinatesScale) {
+        return false;
+    }
+
+    if    get_flag(args, Key::PreserveStrokeWidth)
+       && value_t!(args, KEYS[Key::NormalizeSize], f64).unwrap() == 0.0 {
+        println!("Error: You can use '--{}=true' only with '--{}'.",
+                 KEYS[Key::PreserveStrokeWidth], KEYS[Key::NormalizeSize]);
+        return false;
+    }
+
     true
 }
 
@@ -360,6 +501,7 @@ pub fn gen_cleaning_options(args: &ArgMatches) -> Options {
         remove_dupl_linear_gradients: get_flag(args, Key::RemoveDuplLinearGradients),
         remove_dupl_radial_gradients: get_flag(args, Key::RemoveDuplRadialGradients),
         remove_dupl_fe_gaussian_blur: get_flag(args, Key::RemoveDuplFeGaussianBlur),
+        remove_dupl_elements: get_dupl_elements(args),
         ungroup_groups: get_flag(args, Key::UngroupGroups),
         ungroup_defs: get_flag(args, Key::UngroupDefs),
         group_by_style: get_flag(args, Key::GroupByStyle),
@@ -367,7 +509,14 @@ pub fn gen_cleaning_options(args: &ArgMatches) -> Options {
         regroup_gradient_stops: get_flag(args, Key::RegroupGradientStops),
         remove_invalid_stops: get_flag(args, Key::RemoveInvalidStops),
         remove_invisible_elements: get_flag(args, Key::RemoveInvisibleElements),
+        remove_hidden_elements: get_flag(args, Key::RemoveHiddenElements),
+        remove_unpainted_elements: get_flag(args, Key::RemoveUnpaintedElements),
+        remove_empty_shapes: get_flag(args, Key::RemoveEmptyShapes),
         resolve_use: get_flag(args, Key::ResolveUse),
+        replace_dupl_with_use: get_flag(args, Key::ReplaceDuplWithUse),
+        optimize_filters: get_flag(args, Key::OptimizeFilters),
+        remove_offscreen_elements: get_flag(args, Key::RemoveOffscreenElements),
+        remove_occluded_elements: get_flag(args, Key::RemoveOccludedElements),
 
         remove_version: get_flag(args, Key::RemoveVersion),
         remove_unreferenced_ids: get_flag(args, Key::RemoveUnreferencedIds),
@@ -381,6 +530,13 @@ pub fn gen_cleaning_options(args: &ArgMatches) -> Options {
         join_style_attributes: get_flag(args, Key::JoinStyleAttributes),
         apply_transform_to_gradients: get_flag(args, Key::ApplyTransformToGradients),
         apply_transform_to_shapes: get_flag(args, Key::ApplyTransformToShapes),
+        fit_viewbox: get_flag(args, Key::FitViewbox),
+        fit_viewbox_padding: value_t!(args, KEYS[Key::FitViewboxPadding], f64).unwrap(),
+        normalize_size: value_t!(args, KEYS[Key::NormalizeSize], f64).unwrap(),
+        preserve_stroke_width: get_flag(args, Key::PreserveStrokeWidth),
+        resolve_units: get_flag(args, Key::ResolveUnits),
+        rebase_coordinates: get_flag(args, Key::RebaseCoordinates),
+        rebase_coordinates_scale: get_flag(args, Key::RebaseCoordinatesScale),
 
         paths_to_relative: get_flag(args, Key::PathsToRelative),
         remove_unused_segments: get_flag(args, Key::RemoveUnusedSegments),
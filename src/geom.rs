@@ -0,0 +1,1066 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+//! Bounding box calculation.
+//!
+//! All functions return `None` when a bounding box can't be calculated
+//! (unsupported units, elements or attributes) and `Some(None)` for elements
+//! without a geometry, like empty paths or groups.
+
+use std::f64::consts::{PI, SQRT_2};
+
+use svgdom::{Node, NodeType, AttributeValue, ValueId};
+use svgdom::ElementId as EId;
+use svgdom::AttributeId as AId;
+use svgdom::types::Transform;
+use svgdom::types::LengthUnit as Unit;
+use svgdom::types::path::{Path, SegmentData};
+
+const EPSILON: f64 = 1e-12;
+
+/// A rectangle defined by its top-left and bottom-right corners.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rect {
+    pub x1: f64,
+    pub y1: f64,
+    pub x2: f64,
+    pub y2: f64,
+}
+
+impl Rect {
+    /// Creates a new rectangle from the position and the size.
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect { x1: x, y1: y, x2: x + width, y2: y + height }
+    }
+
+    /// Creates a zero-sized rectangle at the specified point.
+    pub fn from_point(x: f64, y: f64) -> Rect {
+        Rect { x1: x, y1: y, x2: x, y2: y }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.x2 - self.x1
+    }
+
+    pub fn height(&self) -> f64 {
+        self.y2 - self.y1
+    }
+
+    /// Checks that `r` is completely inside the current rectangle.
+    pub fn contains(&self, r: &Rect) -> bool {
+        r.x1 >= self.x1 && r.y1 >= self.y1 && r.x2 <= self.x2 && r.y2 <= self.y2
+    }
+
+    /// Checks that rectangles have a common area.
+    ///
+    /// Rectangles that only touch each other are not intersecting.
+    pub fn intersects(&self, r: &Rect) -> bool {
+        self.x1 < r.x2 && r.x1 < self.x2 && self.y1 < r.y2 && r.y1 < self.y2
+    }
+
+    /// Expands the rectangle to include the specified point.
+    pub fn expand(&mut self, x: f64, y: f64) {
+        self.x1 = self.x1.min(x);
+        self.y1 = self.y1.min(y);
+        self.x2 = self.x2.max(x);
+        self.y2 = self.y2.max(y);
+    }
+
+    /// Returns the smallest rectangle that contains both rectangles.
+    pub fn union(&self, r: &Rect) -> Rect {
+        Rect {
+            x1: self.x1.min(r.x1),
+            y1: self.y1.min(r.y1),
+            x2: self.x2.max(r.x2),
+            y2: self.y2.max(r.y2),
+        }
+    }
+
+    /// Returns the common area of rectangles, if any.
+    pub fn intersection(&self, r: &Rect) -> Option<Rect> {
+        if !self.intersects(r) {
+            return None;
+        }
+
+        Some(Rect {
+            x1: self.x1.max(r.x1),
+            y1: self.y1.max(r.y1),
+            x2: self.x2.min(r.x2),
+            y2: self.y2.min(r.y2),
+        })
+    }
+
+    /// Returns the rectangle enlarged by `d` in each direction.
+    pub fn outset(&self, d: f64) -> Rect {
+        Rect {
+            x1: self.x1 - d,
+            y1: self.y1 - d,
+            x2: self.x2 + d,
+            y2: self.y2 + d,
+        }
+    }
+
+    /// Returns a bounding box of the transformed rectangle.
+    pub fn transform(&self, ts: &Transform) -> Rect {
+        let (x, y) = ts.apply(self.x1, self.y1);
+        let mut r = Rect::from_point(x, y);
+        for &(x, y) in &[(self.x2, self.y1), (self.x2, self.y2), (self.x1, self.y2)] {
+            let (x, y) = ts.apply(x, y);
+            r.expand(x, y);
+        }
+        r
+    }
+}
+
+/// Calculates a bounding box of the node in its own user space.
+///
+/// The node's own `transform` is not applied. For `svg` and `symbol` elements
+/// the bounding box of their content is returned.
+///
+/// When `with_stroke` is set, the stroke is included. The stroke outline is not
+/// calculated precisely, so the result can be bigger than the real one.
+/// Elements with markers or filters return `None` in this mode.
+///
+/// The text bounding box is an approximation, which expects that glyphs
+/// are not wider than the font size.
+pub fn calc_bbox(node: &Node, with_stroke: bool) -> Option<Option<Rect>> {
+    let eid = try_opt!(node.tag_id());
+
+    if with_stroke && node.has_attribute(AId::Filter) {
+        return None;
+    }
+
+    let r = match eid {
+          EId::G
+        | EId::A
+        | EId::Switch
+        | EId::Svg
+        | EId::Symbol => return calc_children_bbox(node, with_stroke),
+        EId::Use => return calc_use_bbox(node, with_stroke),
+        EId::Image => {
+            let attrs = node.attributes();
+            let w = try_opt!(get_num(&attrs, AId::Width));
+            let h = try_opt!(get_num(&attrs, AId::Height));
+            if !(w > 0.0 && h > 0.0) {
+                return Some(None);
+            }
+
+            let x = try_opt!(get_num(&attrs, AId::X));
+            let y = try_opt!(get_num(&attrs, AId::Y));
+            // the stroke is not applicable to images
+            return Some(Some(Rect::new(x, y, w, h)));
+        }
+        EId::Text => try_opt!(calc_text_bbox(node)),
+        _ => try_opt!(calc_geometry_bbox(node)),
+    };
+
+    let r = match r {
+        Some(r) => r,
+        None => return Some(None),
+    };
+
+    if !with_stroke {
+        return Some(Some(r));
+    }
+
+    for aid in &[AId::Marker, AId::MarkerStart, AId::MarkerMid, AId::MarkerEnd] {
+        match resolve_inherited(node, *aid) {
+            Some(AttributeValue::PredefValue(ValueId::None)) | None => {}
+            _ => return None,
+        }
+    }
+
+    let sw = try_opt!(resolve_stroke_width(node));
+    if sw == 0.0 {
+        return Some(Some(r));
+    }
+
+    let factor = try_opt!(stroke_factor(node, eid));
+    Some(Some(r.outset(sw / 2.0 * factor)))
+}
+
+/// Calculates a bounding box of the node in its parent's user space.
+///
+/// Unlike `calc_bbox`, the node's own `transform` is applied.
+///
+/// Returns `None` for `svg` and `symbol` elements, since they establish a new viewport.
+pub fn calc_transformed_bbox(node: &Node, with_stroke: bool) -> Option<Option<Rect>> {
+    if node.is_tag_name(EId::Svg) || node.is_tag_name(EId::Symbol) {
+        return None;
+    }
+
+    let ts = try_opt!(get_transform(node));
+
+    match try_opt!(calc_bbox(node, with_stroke)) {
+        Some(r) => Some(Some(r.transform(&ts))),
+        None => Some(None),
+    }
+}
+
+/// Calculates a bounding box of the node in the root `svg` element's user space.
+///
+/// Transforms of all ancestors are applied. Returns `None` for elements that are
+/// not rendered directly, like elements inside `defs`, `clipPath` or `mask`,
+/// and for elements inside nested `svg` elements.
+pub fn calc_canvas_bbox(node: &Node, with_stroke: bool) -> Option<Option<Rect>> {
+    if node.is_tag_name(EId::Svg) || node.is_tag_name(EId::Symbol) {
+        return None;
+    }
+
+    let ts = try_opt!(calc_canvas_transform(node));
+
+    match try_opt!(calc_bbox(node, with_stroke)) {
+        Some(r) => Some(Some(r.transform(&ts))),
+        None => Some(None),
+    }
+}
+
+/// Calculates a transform from the node's user space to the root `svg` element's user space.
+///
+/// The node's own `transform` is included. Returns `None` in the same cases
+/// as `calc_canvas_bbox`.
+pub fn calc_canvas_transform(node: &Node) -> Option<Transform> {
+    let mut ts = try_opt!(get_transform(node));
+
+    for parent in node.parents() {
+        match try_opt!(parent.tag_id()) {
+            EId::G | EId::A | EId::Switch => {}
+            EId::Svg if !parent.parents().any(|n| n.is_tag_name(EId::Svg)) => break,
+            _ => return None,
+        }
+
+        let mut parent_ts = try_opt!(get_transform(&parent));
+        parent_ts.append(&ts);
+        ts = parent_ts;
+    }
+
+    Some(ts)
+}
+
+/// Calculates a bounding box of the basic shape or path without a stroke.
+///
+/// Unlike `calc_bbox`, returns `None` for any other element
+/// and for shapes with a zero width or height.
+pub fn calc_shape_bbox(node: &Node) -> Option<Rect> {
+    if !(node.is_basic_shape() || node.is_tag_name(EId::Path)) {
+        return None;
+    }
+
+    let r = try_opt!(try_opt!(calc_geometry_bbox(node)));
+
+    if r.width() == 0.0 || r.height() == 0.0 {
+        return None;
+    }
+
+    Some(r)
+}
+
+/// Calculates a bounding box of the path.
+///
+/// Curves and arcs are processed precisely, using their extreme points.
+///
+/// Returns `None` for an empty path.
+pub fn calc_path_bbox(path: &Path) -> Option<Rect> {
+    let mut path = path.clone();
+    path.conv_to_absolute();
+
+    let mut r: Option<Rect> = None;
+
+    // current point
+    let mut px = 0.0;
+    let mut py = 0.0;
+    // subpath start point
+    let mut sx = 0.0;
+    let mut sy = 0.0;
+    // the last control point, used to resolve smooth curves
+    let mut cx = 0.0;
+    let mut cy = 0.0;
+    let mut prev_is_cubic = false;
+    let mut prev_is_quad = false;
+
+    for seg in &path.d {
+        // a valid path always starts with a MoveTo
+        if r.is_none() {
+            if let SegmentData::MoveTo { x, y } = *seg.data() {
+                r = Some(Rect::from_point(x, y));
+            }
+        }
+        let rect = r.get_or_insert(Rect::from_point(px, py));
+
+        let mut is_cubic = false;
+        let mut is_quad = false;
+
+        match *seg.data() {
+            SegmentData::MoveTo { x, y } => {
+                rect.expand(x, y);
+                sx = x;
+                sy = y;
+                px = x;
+                py = y;
+            }
+            SegmentData::LineTo { x, y } => {
+                rect.expand(x, y);
+                px = x;
+                py = y;
+            }
+            SegmentData::HorizontalLineTo { x } => {
+                rect.expand(x, py);
+                px = x;
+            }
+            SegmentData::VerticalLineTo { y } => {
+                rect.expand(px, y);
+                py = y;
+            }
+            SegmentData::CurveTo { x1, y1, x2, y2, x, y } => {
+                is_cubic = true;
+                expand_by_cubic(rect, (px, py), (x1, y1), (x2, y2), (x, y));
+                cx = x2;
+                cy = y2;
+                px = x;
+                py = y;
+            }
+            SegmentData::SmoothCurveTo { x2, y2, x, y } => {
+                is_cubic = true;
+                // the first control point is a reflection of the previous one
+                let (x1, y1) = if prev_is_cubic { (px * 2.0 - cx, py * 2.0 - cy) } else { (px, py) };
+                expand_by_cubic(rect, (px, py), (x1, y1), (x2, y2), (x, y));
+                cx = x2;
+                cy = y2;
+                px = x;
+                py = y;
+            }
+            SegmentData::Quadratic { x1, y1, x, y } => {
+                is_quad = true;
+                expand_by_quad(rect, (px, py), (x1, y1), (x, y));
+                cx = x1;
+                cy = y1;
+                px = x;
+                py = y;
+            }
+            SegmentData::SmoothQuadratic { x, y } => {
+                is_quad = true;
+                let (x1, y1) = if prev_is_quad { (px * 2.0 - cx, py * 2.0 - cy) } else { (px, py) };
+                expand_by_quad(rect, (px, py), (x1, y1), (x, y));
+                cx = x1;
+                cy = y1;
+                px = x;
+                py = y;
+            }
+            SegmentData::EllipticalArc { rx, ry, x_axis_rotation, large_arc, sweep, x, y } => {
+                expand_by_arc(rect, (px, py), (rx, ry), x_axis_rotation, (large_arc, sweep), (x, y));
+                px = x;
+                py = y;
+            }
+            SegmentData::ClosePath => {
+                px = sx;
+                py = sy;
+            }
+        }
+
+        prev_is_cubic = is_cubic;
+        prev_is_quad = is_quad;
+    }
+
+    r
+}
+
+/// Resolves the stroke width of the node.
+///
+/// Returns zero when the stroke is not set and `None` when the width
+/// can't be resolved.
+pub fn resolve_stroke_width(node: &Node) -> Option<f64> {
+    match resolve_inherited(node, AId::Stroke) {
+        Some(AttributeValue::PredefValue(ValueId::None)) | None => return Some(0.0),
+        _ => {}
+    }
+
+    match resolve_inherited(node, AId::StrokeWidth) {
+        Some(AttributeValue::Length(l)) if l.unit == Unit::None => Some(l.num),
+        Some(_) => None,
+        None => Some(1.0),
+    }
+}
+
+fn calc_children_bbox(node: &Node, with_stroke: bool) -> Option<Option<Rect>> {
+    let mut bbox: Option<Rect> = None;
+
+    for child in node.children().svg() {
+        match try_opt!(child.tag_id()) {
+              EId::Title
+            | EId::Desc
+            | EId::Metadata
+            | EId::Defs
+            | EId::Style
+            | EId::Script
+            | EId::LinearGradient
+            | EId::RadialGradient
+            | EId::Pattern
+            | EId::ClipPath
+            | EId::Mask
+            | EId::Marker
+            | EId::Symbol
+            | EId::Filter => continue,
+            _ => {}
+        }
+
+        if child.has_attribute_with_value(AId::Display, ValueId::None) {
+            continue;
+        }
+
+        if let Some(r) = try_opt!(calc_transformed_bbox(&child, with_stroke)) {
+            bbox = Some(match bbox {
+                Some(b) => b.union(&r),
+                None => r,
+            });
+        }
+    }
+
+    Some(bbox)
+}
+
+fn calc_use_bbox(node: &Node, with_stroke: bool) -> Option<Option<Rect>> {
+    let link = match node.attribute_value(AId::XlinkHref) {
+        Some(AttributeValue::Link(link)) => link,
+        _ => return Some(None),
+    };
+
+    // elements with a viewport are not supported
+    if link.is_tag_name(EId::Symbol) || link.is_tag_name(EId::Svg) {
+        return None;
+    }
+
+    // The linked element inherits properties from the 'use' element and not from
+    // its own parents, so they must be the same to use the linked element bbox.
+    try_opt!(check_use_inheritance(node, &link, with_stroke));
+
+    let r = match try_opt!(calc_transformed_bbox(&link, with_stroke)) {
+        Some(r) => r,
+        None => return Some(None),
+    };
+
+    let attrs = node.attributes();
+    let x = try_opt!(get_num(&attrs, AId::X));
+    let y = try_opt!(get_num(&attrs, AId::Y));
+
+    Some(Some(Rect { x1: r.x1 + x, y1: r.y1 + y, x2: r.x2 + x, y2: r.y2 + y }))
+}
+
+fn check_use_inheritance(node: &Node, link: &Node, with_stroke: bool) -> Option<()> {
+    let link_parent = try_opt!(link.parent());
+
+    let mut list = vec![AId::FontSize, AId::TextAnchor];
+    if with_stroke {
+        list.extend_from_slice(&[AId::Stroke, AId::StrokeWidth, AId::StrokeLinejoin,
+                                 AId::StrokeLinecap, AId::StrokeMiterlimit,
+                                 AId::Marker, AId::MarkerStart, AId::MarkerMid, AId::MarkerEnd]);
+    }
+
+    for aid in list {
+        if resolve_inherited(node, aid) != resolve_inherited(&link_parent, aid) {
+            return None;
+        }
+    }
+
+    Some(())
+}
+
+// Calculates a bounding box of the basic shape or path.
+fn calc_geometry_bbox(node: &Node) -> Option<Option<Rect>> {
+    let attrs = node.attributes();
+
+    let r = match try_opt!(node.tag_id()) {
+        EId::Rect => {
+            let w = try_opt!(get_num(&attrs, AId::Width));
+            let h = try_opt!(get_num(&attrs, AId::Height));
+            if !(w > 0.0 && h > 0.0) {
+                return Some(None);
+            }
+
+            let x = try_opt!(get_num(&attrs, AId::X));
+            let y = try_opt!(get_num(&attrs, AId::Y));
+            Rect::new(x, y, w, h)
+        }
+        EId::Circle => {
+            let r = try_opt!(get_num(&attrs, AId::R));
+            if !(r > 0.0) {
+                return Some(None);
+            }
+
+            let cx = try_opt!(get_num(&attrs, AId::Cx));
+            let cy = try_opt!(get_num(&attrs, AId::Cy));
+            Rect { x1: cx - r, y1: cy - r, x2: cx + r, y2: cy + r }
+        }
+        EId::Ellipse => {
+            let rx = try_opt!(get_num(&attrs, AId::Rx));
+            let ry = try_opt!(get_num(&attrs, AId::Ry));
+            if !(rx > 0.0 && ry > 0.0) {
+                return Some(None);
+            }
+
+            let cx = try_opt!(get_num(&attrs, AId::Cx));
+            let cy = try_opt!(get_num(&attrs, AId::Cy));
+            Rect { x1: cx - rx, y1: cy - ry, x2: cx + rx, y2: cy + ry }
+        }
+        EId::Line => {
+            let x1 = try_opt!(get_num(&attrs, AId::X1));
+            let y1 = try_opt!(get_num(&attrs, AId::Y1));
+            let x2 = try_opt!(get_num(&attrs, AId::X2));
+            let y2 = try_opt!(get_num(&attrs, AId::Y2));
+            let mut r = Rect::from_point(x1, y1);
+            r.expand(x2, y2);
+            r
+        }
+        EId::Polyline | EId::Polygon => {
+            let points = match attrs.get_value(AId::Points) {
+                Some(&AttributeValue::NumberList(ref points)) => points,
+                _ => return Some(None),
+            };
+
+            if points.len() < 2 {
+                return Some(None);
+            }
+
+            let mut r = Rect::from_point(points[0], points[1]);
+            for p in points.chunks(2) {
+                if p.len() == 2 {
+                    r.expand(p[0], p[1]);
+                }
+            }
+            r
+        }
+        EId::Path => {
+            match attrs.get_value(AId::D) {
+                Some(&AttributeValue::Path(ref path)) => return Some(calc_path_bbox(path)),
+                _ => return Some(None),
+            }
+        }
+        _ => return None,
+    };
+
+    Some(Some(r))
+}
+
+// Approximates a bounding box of the text.
+//
+// Only a single line of horizontal text without per-glyph positioning is supported.
+fn calc_text_bbox(node: &Node) -> Option<Option<Rect>> {
+    let mut width = 0.0;
+    let mut max_font_size = 0.0;
+
+    for n in node.descendants() {
+        if n.node_type() == NodeType::Text {
+            // unwrap is safe, because a text node always has a parent
+            let parent = n.parent().unwrap();
+            let font_size = try_opt!(resolve_font_size(&parent));
+            width += n.text().trim().chars().count() as f64 * font_size;
+            if font_size > max_font_size {
+                max_font_size = font_size;
+            }
+        } else if n.node_type() == NodeType::Element {
+            if n != *node && !n.is_tag_name(EId::Tspan) {
+                return None;
+            }
+
+            if n.has_attributes(&[AId::Dx, AId::Dy, AId::Rotate, AId::WritingMode])
+               || (n != *node && n.has_attributes(&[AId::X, AId::Y])) {
+                return None;
+            }
+        }
+    }
+
+    if width == 0.0 {
+        return Some(None);
+    }
+
+    let x = try_opt!(get_first_num(node, AId::X));
+    let y = try_opt!(get_first_num(node, AId::Y));
+
+    let x1 = match resolve_inherited(node, AId::TextAnchor) {
+        Some(AttributeValue::PredefValue(ValueId::Middle)) => x - width / 2.0,
+        Some(AttributeValue::PredefValue(ValueId::End)) => x - width,
+        _ => x,
+    };
+
+    // 'y' is a baseline position, so we reserve the whole font size
+    // for the ascent and a quarter of it for the descent
+    Some(Some(Rect {
+        x1: x1,
+        y1: y - max_font_size,
+        x2: x1 + width,
+        y2: y + max_font_size * 0.25,
+    }))
+}
+
+// Returns the maximum distance, relative to the half of the stroke width,
+// by which the stroke can go outside of the shape.
+fn stroke_factor(node: &Node, eid: EId) -> Option<f64> {
+    match eid {
+        // joins of the rectangle corners are always inside the half-width outline
+        EId::Rect | EId::Circle | EId::Ellipse => return Some(1.0),
+        _ => {}
+    }
+
+    let mut factor: f64 = 1.0;
+
+    if eid != EId::Line {
+        match resolve_inherited(node, AId::StrokeLinejoin) {
+            Some(AttributeValue::PredefValue(ValueId::Miter)) | None => {
+                let limit = match resolve_inherited(node, AId::StrokeMiterlimit) {
+                    Some(AttributeValue::Number(n)) => n,
+                    Some(_) => return None,
+                    None => 4.0,
+                };
+                factor = factor.max(limit);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(AttributeValue::PredefValue(ValueId::Square)) =
+        resolve_inherited(node, AId::StrokeLinecap) {
+        factor = factor.max(SQRT_2);
+    }
+
+    Some(factor)
+}
+
+fn get_transform(node: &Node) -> Option<Transform> {
+    match node.attribute_value(AId::Transform) {
+        Some(AttributeValue::Transform(ts)) => Some(ts),
+        Some(_) => None,
+        None => Some(Transform::default()),
+    }
+}
+
+fn resolve_font_size(node: &Node) -> Option<f64> {
+    match resolve_inherited(node, AId::FontSize) {
+        Some(AttributeValue::Length(l)) if l.unit == Unit::None || l.unit == Unit::Px => {
+            Some(l.num)
+        }
+        Some(_) => None,
+        // 'medium'
+        None => Some(16.0),
+    }
+}
+
+/// Returns the attribute value of the node or of the nearest parent, which has it.
+pub fn resolve_inherited(node: &Node, aid: AId) -> Option<AttributeValue> {
+    if let Some(v) = node.attribute_value(aid) {
+        return Some(v);
+    }
+
+    match node.parents().find(|n| n.has_attribute(aid)) {
+        Some(n) => n.attribute_value(aid),
+        None => None,
+    }
+}
+
+// Returns the unitless value of the attribute or zero if it's not set.
+fn get_num(attrs: &::svgdom::Attributes, aid: AId) -> Option<f64> {
+    match attrs.get_value(aid) {
+        Some(&AttributeValue::Length(l)) if l.unit == Unit::None => Some(l.num),
+        Some(&AttributeValue::Number(n)) => Some(n),
+        Some(_) => None,
+        None => Some(0.0),
+    }
+}
+
+// Like `get_num`, but also supports lists, like text coordinates.
+fn get_first_num(node: &Node, aid: AId) -> Option<f64> {
+    let attrs = node.attributes();
+    match attrs.get_value(aid) {
+        Some(&AttributeValue::LengthList(ref list)) => {
+            match list.first() {
+                Some(l) if l.unit == Unit::None && list.len() == 1 => Some(l.num),
+                Some(_) => None,
+                None => Some(0.0),
+            }
+        }
+        _ => get_num(&attrs, aid),
+    }
+}
+
+fn expand_by_cubic(r: &mut Rect, p0: (f64, f64), p1: (f64, f64),
+                   p2: (f64, f64), p3: (f64, f64)) {
+    r.expand(p3.0, p3.1);
+
+    let calc = |p0: f64, p1: f64, p2: f64, p3: f64, t: f64| {
+        let mt = 1.0 - t;
+        mt * mt * mt * p0 + 3.0 * mt * mt * t * p1 + 3.0 * mt * t * t * p2 + t * t * t * p3
+    };
+
+    // roots of the curve derivative for each axis
+    let mut ts = Vec::with_capacity(4);
+    for &(v0, v1, v2, v3) in &[(p0.0, p1.0, p2.0, p3.0), (p0.1, p1.1, p2.1, p3.1)] {
+        let a = v3 - 3.0 * v2 + 3.0 * v1 - v0;
+        let b = 2.0 * (v2 - 2.0 * v1 + v0);
+        let c = v1 - v0;
+        solve_quadratic(a, b, c, &mut ts);
+    }
+
+    for t in ts {
+        if t > 0.0 && t < 1.0 {
+            r.expand(calc(p0.0, p1.0, p2.0, p3.0, t), calc(p0.1, p1.1, p2.1, p3.1, t));
+        }
+    }
+}
+
+fn expand_by_quad(r: &mut Rect, p0: (f64, f64), p1: (f64, f64), p2: (f64, f64)) {
+    r.expand(p2.0, p2.1);
+
+    let calc = |p0: f64, p1: f64, p2: f64, t: f64| {
+        let mt = 1.0 - t;
+        mt * mt * p0 + 2.0 * mt * t * p1 + t * t * p2
+    };
+
+    for &(v0, v1, v2) in &[(p0.0, p1.0, p2.0), (p0.1, p1.1, p2.1)] {
+        let d = v0 - 2.0 * v1 + v2;
+        if d.abs() > EPSILON {
+            let t = (v0 - v1) / d;
+            if t > 0.0 && t < 1.0 {
+                r.expand(calc(p0.0, p1.0, p2.0, t), calc(p0.1, p1.1, p2.1, t));
+            }
+        }
+    }
+}
+
+// See https://www.w3.org/TR/SVG/implnote.html#ArcConversionEndpointToCenter
+fn expand_by_arc(r: &mut Rect, p1: (f64, f64), radii: (f64, f64), angle: f64,
+                 flags: (bool, bool), p2: (f64, f64)) {
+    r.expand(p2.0, p2.1);
+
+    let mut rx = radii.0.abs();
+    let mut ry = radii.1.abs();
+    let (large_arc, sweep) = flags;
+
+    // an arc with a zero radius is a line and an arc with equal endpoints is omitted
+    if rx == 0.0 || ry == 0.0 || (p1.0 == p2.0 && p1.1 == p2.1) {
+        return;
+    }
+
+    let (sin, cos) = angle.to_radians().sin_cos();
+
+    let dx2 = (p1.0 - p2.0) / 2.0;
+    let dy2 = (p1.1 - p2.1) / 2.0;
+    let x1p = cos * dx2 + sin * dy2;
+    let y1p = -sin * dx2 + cos * dy2;
+
+    // scale up radii that are too small
+    let lambda = (x1p * x1p) / (rx * rx) + (y1p * y1p) / (ry * ry);
+    if lambda > 1.0 {
+        let s = lambda.sqrt();
+        rx *= s;
+        ry *= s;
+    }
+
+    let rx2 = rx * rx;
+    let ry2 = ry * ry;
+    let num = rx2 * ry2 - rx2 * y1p * y1p - ry2 * x1p * x1p;
+    let den = rx2 * y1p * y1p + ry2 * x1p * x1p;
+    let mut k = (num / den).max(0.0).sqrt();
+    if large_arc == sweep {
+        k = -k;
+    }
+
+    let cxp = k * rx * y1p / ry;
+    let cyp = -k * ry * x1p / rx;
+    let cx = cos * cxp - sin * cyp + (p1.0 + p2.0) / 2.0;
+    let cy = sin * cxp + cos * cyp + (p1.1 + p2.1) / 2.0;
+
+    let ux = (x1p - cxp) / rx;
+    let uy = (y1p - cyp) / ry;
+    let vx = (-x1p - cxp) / rx;
+    let vy = (-y1p - cyp) / ry;
+
+    let theta1 = vec_angle(1.0, 0.0, ux, uy);
+    let mut delta = vec_angle(ux, uy, vx, vy);
+    if !sweep && delta > 0.0 {
+        delta -= 2.0 * PI;
+    } else if sweep && delta < 0.0 {
+        delta += 2.0 * PI;
+    }
+
+    let in_sweep = |theta: f64| {
+        let d = if delta >= 0.0 { theta - theta1 } else { theta1 - theta };
+        let mut d = d % (2.0 * PI);
+        if d < 0.0 {
+            d += 2.0 * PI;
+        }
+        d <= delta.abs()
+    };
+
+    // angles at which the ellipse reaches its extreme points
+    let tx = (-ry * sin).atan2(rx * cos);
+    let ty = (ry * cos).atan2(rx * sin);
+
+    for &theta in &[tx, tx + PI, ty, ty + PI] {
+        if in_sweep(theta) {
+            let (s, c) = theta.sin_cos();
+            let x = cx + rx * cos * c - ry * sin * s;
+            let y = cy + rx * sin * c + ry * cos * s;
+            r.expand(x, y);
+        }
+    }
+}
+
+fn vec_angle(ux: f64, uy: f64, vx: f64, vy: f64) -> f64 {
+    (ux * vy - uy * vx).atan2(ux * vx + uy * vy)
+}
+
+fn solve_quadratic(a: f64, b: f64, c: f64, roots: &mut Vec<f64>) {
+    if a.abs() < EPSILON {
+        if b.abs() > EPSILON {
+            roots.push(-c / b);
+        }
+        return;
+    }
+
+    let d = b * b - 4.0 * a * c;
+    if d < 0.0 {
+        return;
+    }
+
+    let d = d.sqrt();
+    roots.push((-b + d) / (2.0 * a));
+    roots.push((-b - d) / (2.0 * a));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::Document;
+
+    fn get_node(doc: &Document, id: &str) -> Node {
+        doc.descendants().svg().find(|n| *n.id() == id).unwrap()
+    }
+
+    fn assert_rect(r: Rect, x1: f64, y1: f64, x2: f64, y2: f64) {
+        let e = 1e-6;
+        assert!((r.x1 - x1).abs() < e && (r.y1 - y1).abs() < e
+                && (r.x2 - x2).abs() < e && (r.y2 - y2).abs() < e,
+                "{:?} != ({}, {}, {}, {})", r, x1, y1, x2, y2);
+    }
+
+    macro_rules! test_bbox {
+        ($name:ident, $func:ident, $with_stroke:expr, $in_text:expr, $rect:expr) => (
+            #[test]
+            fn $name() {
+                let doc = Document::from_data($in_text).unwrap();
+                let node = get_node(&doc, "e");
+                let r = $func(&node, $with_stroke).unwrap().unwrap();
+                let (x1, y1, x2, y2) = $rect;
+                assert_rect(r, x1, y1, x2, y2);
+            }
+        )
+    }
+
+    test_bbox!(path_lines, calc_bbox, false,
+b"<svg>
+    <path id='e' d='M 10 20 L 30 5 H 15 V 40 Z'/>
+</svg>",
+    (10.0, 5.0, 30.0, 40.0));
+
+    // the extreme point is at t = 0.5
+    test_bbox!(path_cubic, calc_bbox, false,
+b"<svg>
+    <path id='e' d='M 0 0 C 0 100 100 100 100 0'/>
+</svg>",
+    (0.0, 0.0, 100.0, 75.0));
+
+    // the first curve reaches its maximum at t = 2 - sqrt(2), which gives 200 * (sqrt(2) - 1),
+    // and the reflected control point of the second one is (50 0)
+    test_bbox!(path_smooth_cubic, calc_bbox, false,
+b"<svg>
+    <path id='e' d='M 0 0 C 0 100 50 100 50 50 S 100 0 100 50'/>
+</svg>",
+    (0.0, 0.0, 100.0, 82.842712474619));
+
+    test_bbox!(path_quadratic, calc_bbox, false,
+b"<svg>
+    <path id='e' d='M 0 0 Q 50 100 100 0'/>
+</svg>",
+    (0.0, 0.0, 100.0, 50.0));
+
+    test_bbox!(path_smooth_quadratic, calc_bbox, false,
+b"<svg>
+    <path id='e' d='m 0 0 q 50 100 100 0 t 100 0'/>
+</svg>",
+    (0.0, -50.0, 200.0, 50.0));
+
+    test_bbox!(path_arc_1, calc_bbox, false,
+b"<svg>
+    <path id='e' d='M 0 50 A 50 50 0 0 1 100 50'/>
+</svg>",
+    (0.0, 0.0, 100.0, 50.0));
+
+    test_bbox!(path_arc_2, calc_bbox, false,
+b"<svg>
+    <path id='e' d='M 0 50 A 50 50 0 0 0 100 50'/>
+</svg>",
+    (0.0, 50.0, 100.0, 100.0));
+
+    // a half of the ellipse with a vertical major axis
+    test_bbox!(path_arc_3, calc_bbox, false,
+b"<svg>
+    <path id='e' d='M 0 0 A 50 25 90 0 1 0 100'/>
+</svg>",
+    (0.0, 0.0, 25.0, 100.0));
+
+    // radii are too small, so they should be scaled up
+    test_bbox!(path_arc_4, calc_bbox, false,
+b"<svg>
+    <path id='e' d='M 0 0 A 10 10 0 0 1 100 0'/>
+</svg>",
+    (0.0, -50.0, 100.0, 0.0));
+
+    test_bbox!(rect_stroke, calc_bbox, true,
+b"<svg>
+    <rect id='e' height='20' stroke='#000000' stroke-width='4' width='20' x='10' y='10'/>
+</svg>",
+    (8.0, 8.0, 32.0, 32.0));
+
+    // the default miter limit is 4
+    test_bbox!(path_stroke_miter, calc_bbox, true,
+b"<svg>
+    <path id='e' d='M 0 0 L 10 0' stroke='#000000' stroke-width='2'/>
+</svg>",
+    (-4.0, -4.0, 14.0, 4.0));
+
+    test_bbox!(path_stroke_round, calc_bbox, true,
+b"<svg>
+    <g stroke='#000000' stroke-linejoin='round' stroke-width='2'>
+        <path id='e' d='M 0 0 L 10 0'/>
+    </g>
+</svg>",
+    (-1.0, -1.0, 11.0, 1.0));
+
+    test_bbox!(circle_1, calc_bbox, false,
+b"<svg>
+    <circle id='e' cx='10' cy='20' r='5' transform='scale(2)'/>
+</svg>",
+    (5.0, 15.0, 15.0, 25.0));
+
+    test_bbox!(circle_transformed, calc_transformed_bbox, false,
+b"<svg>
+    <circle id='e' cx='10' cy='20' r='5' transform='scale(2)'/>
+</svg>",
+    (10.0, 30.0, 30.0, 50.0));
+
+    test_bbox!(group_1, calc_transformed_bbox, false,
+b"<svg>
+    <g id='e' transform='translate(10 20)'>
+        <rect height='10' width='10'/>
+        <rect display='none' height='100' width='100'/>
+        <title>Title</title>
+    </g>
+</svg>",
+    (10.0, 20.0, 20.0, 30.0));
+
+    test_bbox!(group_rotated, calc_transformed_bbox, false,
+b"<svg>
+    <g id='e' transform='rotate(90)'>
+        <rect height='20' width='10'/>
+    </g>
+</svg>",
+    (-20.0, 0.0, 0.0, 10.0));
+
+    test_bbox!(canvas_1, calc_canvas_bbox, false,
+b"<svg>
+    <g transform='scale(2)'>
+        <g transform='translate(5 5)'>
+            <rect id='e' height='10' width='10'/>
+        </g>
+    </g>
+</svg>",
+    (10.0, 10.0, 30.0, 30.0));
+
+    test_bbox!(use_1, calc_transformed_bbox, false,
+b"<svg xmlns:xlink='http://www.w3.org/1999/xlink'>
+    <defs>
+        <rect id='r1' height='10' width='10'/>
+    </defs>
+    <use id='e' transform='scale(2)' x='5' xlink:href='#r1' y='5'/>
+</svg>",
+    (10.0, 10.0, 30.0, 30.0));
+
+    test_bbox!(text_1, calc_bbox, false,
+b"<svg>
+    <text id='e' font-size='10' x='10' y='20'>abc</text>
+</svg>",
+    (10.0, 10.0, 40.0, 22.5));
+
+    test_bbox!(text_2, calc_bbox, false,
+b"<svg>
+    <text id='e' font-size='10' text-anchor='middle' x='50' y='20'>ab<tspan>cd</tspan></text>
+</svg>",
+    (30.0, 10.0, 70.0, 22.5));
+
+    #[test]
+    fn empty_group() {
+        let doc = Document::from_data(b"<svg><g id='e'/></svg>").unwrap();
+        assert_eq!(calc_bbox(&get_node(&doc, "e"), true), Some(None));
+    }
+
+    #[test]
+    fn canvas_in_defs() {
+        let doc = Document::from_data(
+            b"<svg><defs><rect id='e' height='10' width='10'/></defs></svg>").unwrap();
+        assert_eq!(calc_canvas_bbox(&get_node(&doc, "e"), false), None);
+    }
+
+    #[test]
+    fn markers() {
+        let doc = Document::from_data(
+            b"<svg><path id='e' d='M 0 0 L 10 0' marker-end='url(#m1)' stroke='#000000'/>
+              <marker id='m1'/></svg>").unwrap();
+        let node = get_node(&doc, "e");
+        assert_eq!(calc_bbox(&node, true), None);
+        assert_eq!(calc_bbox(&node, false), Some(Some(Rect::new(0.0, 0.0, 10.0, 0.0))));
+    }
+
+    #[test]
+    fn use_stroke() {
+        let doc = Document::from_data(
+            b"<svg><defs><rect id='r1' height='10' width='10'/></defs>
+              <use id='e' stroke='#000000' stroke-width='50' xlink:href='#r1'/></svg>").unwrap();
+        let node = get_node(&doc, "e");
+        assert_eq!(calc_bbox(&node, true), None);
+        assert_eq!(calc_bbox(&node, false), Some(Some(Rect::new(0.0, 0.0, 10.0, 10.0))));
+    }
+
+    #[test]
+    fn positioned_tspan() {
+        let doc = Document::from_data(
+            b"<svg><text id='e'>a<tspan x='100'>b</tspan></text></svg>").unwrap();
+        assert_eq!(calc_bbox(&get_node(&doc, "e"), false), None);
+    }
+
+    #[test]
+    fn rect_methods() {
+        let r = Rect::new(0.0, 0.0, 10.0, 10.0);
+        assert!(r.contains(&Rect::new(1.0, 1.0, 5.0, 5.0)));
+        assert!(!r.contains(&Rect::new(5.0, 5.0, 10.0, 10.0)));
+        assert!(r.intersects(&Rect::new(5.0, 5.0, 10.0, 10.0)));
+        assert!(!r.intersects(&Rect::new(10.0, 0.0, 10.0, 10.0)));
+        assert_eq!(r.union(&Rect::new(-5.0, 5.0, 10.0, 10.0)), Rect::new(-5.0, 0.0, 15.0, 15.0));
+    }
+}
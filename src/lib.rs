@@ -29,12 +29,17 @@ extern crate svgdom_utils;
 pub use svgdom::{ParseOptions, WriteOptions};
 
 pub use options::Options as CleaningOptions;
+pub use options::DuplElements;
 pub use error::Error;
 
+#[macro_use]
+mod macros;
+
 #[cfg(feature = "cli-parsing")]
 pub mod cli;
 
 pub mod cleaner;
+pub mod geom;
 mod error;
 mod options;
 mod task;
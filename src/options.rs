@@ -30,6 +30,7 @@ pub struct Options {
     pub remove_dupl_linear_gradients: bool,
     pub remove_dupl_radial_gradients: bool,
     pub remove_dupl_fe_gaussian_blur: bool,
+    pub remove_dupl_elements: DuplElements,
     pub ungroup_groups: bool,
     pub ungroup_defs: bool,
     pub group_by_style: bool,
@@ -37,7 +38,14 @@ pub struct Options {
     pub regroup_gradient_stops: bool,
     pub remove_invalid_stops: bool,
     pub remove_invisible_elements: bool,
+    pub remove_hidden_elements: bool,
+    pub remove_unpainted_elements: bool,
+    pub remove_empty_shapes: bool,
     pub resolve_use: bool,
+    pub replace_dupl_with_use: bool,
+    pub optimize_filters: bool,
+    pub remove_offscreen_elements: bool,
+    pub remove_occluded_elements: bool,
 
     pub remove_version: bool,
     pub remove_unreferenced_ids: bool,
@@ -51,6 +59,13 @@ pub struct Options {
     pub join_style_attributes: bool,
     pub apply_transform_to_gradients: bool,
     pub apply_transform_to_shapes: bool,
+    pub fit_viewbox: bool,
+    pub fit_viewbox_padding: f64,
+    pub normalize_size: f64,
+    pub preserve_stroke_width: bool,
+    pub resolve_units: bool,
+    pub rebase_coordinates: bool,
+    pub rebase_coordinates_scale: bool,
 
     pub paths_to_relative: bool,
     pub remove_unused_segments: bool,
@@ -68,6 +83,7 @@ impl Default for Options {
             remove_dupl_linear_gradients: false,
             remove_dupl_radial_gradients: false,
             remove_dupl_fe_gaussian_blur: false,
+            remove_dupl_elements: DuplElements::default(),
             ungroup_groups: false,
             ungroup_defs: false,
             group_by_style: false,
@@ -75,7 +91,14 @@ impl Default for Options {
             regroup_gradient_stops: false,
             remove_invalid_stops: false,
             remove_invisible_elements: false,
+            remove_hidden_elements: false,
+            remove_unpainted_elements: false,
+            remove_empty_shapes: false,
             resolve_use: false,
+            replace_dupl_with_use: false,
+            optimize_filters: false,
+            remove_offscreen_elements: false,
+            remove_occluded_elements: false,
 
             remove_version: false,
             remove_unreferenced_ids: false,
@@ -89,6 +112,13 @@ impl Default for Options {
             join_style_attributes: false,
             apply_transform_to_gradients: false,
             apply_transform_to_shapes: false,
+            fit_viewbox: false,
+            fit_viewbox_padding: 0.0,
+            normalize_size: 0.0,
+            preserve_stroke_width: false,
+            resolve_units: false,
+            rebase_coordinates: false,
+            rebase_coordinates_scale: false,
 
             paths_to_relative: false,
             remove_unused_segments: false,
@@ -96,3 +126,30 @@ impl Default for Options {
         }
     }
 }
+
+/// List of elements that should be processed by the `remove_dupl_elements`.
+#[derive(Clone, Copy, Default)]
+pub struct DuplElements {
+    pub linear_gradient: bool,
+    pub radial_gradient: bool,
+    pub pattern: bool,
+    pub clip_path: bool,
+    pub mask: bool,
+    pub marker: bool,
+    pub symbol: bool,
+    pub filter: bool,
+}
+
+impl DuplElements {
+    /// Returns `true` if at least one element type is enabled.
+    pub fn is_any(&self) -> bool {
+           self.linear_gradient
+        || self.radial_gradient
+        || self.pattern
+        || self.clip_path
+        || self.mask
+        || self.marker
+        || self.symbol
+        || self.filter
+    }
+}
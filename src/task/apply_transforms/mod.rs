@@ -29,8 +29,9 @@ mod shapes;
 pub mod utils {
     use task::short::{EId, AId, Unit};
 
-    use svgdom::{Node, Attributes, AttributeValue};
-    use svgdom::types::{Length, Transform};
+    use svgdom::{Node, Attributes, AttributeValue, ValueId};
+    use svgdom::types::{Length, Transform, FuzzyEq};
+    use svgdom::types::path::{Path, Segment, SegmentData};
 
     // TODO: remove
     pub fn has_valid_transform(node: &Node) -> bool {
@@ -49,7 +50,8 @@ pub mod utils {
             return false;
         }
 
-        // TODO: is rotate supported?
+        // NOTE: rotation is checked separately via 'is_valid_rotation',
+        //       since it can't be applied to all shapes.
 
         // If transform has skew part - we should skip it,
         // because it can be applied only to a raster.
@@ -60,9 +62,20 @@ pub mod utils {
         true
     }
 
+    // Checks that transform contains only translate, rotate and proportional scale parts.
+    //
+    // Such transform preserves angles and proportions, so it can be applied
+    // to circles, lines and polygon-like paths.
+    pub fn is_valid_rotation(ts: &Transform) -> bool {
+        // a == d and b == -c for any combination of rotate and proportional scale
+        ts.a.fuzzy_eq(&ts.d) && ts.b.fuzzy_eq(&-ts.c)
+    }
+
+    pub fn has_rotation(ts: &Transform) -> bool {
+        ts.b.fuzzy_ne(&0.0) || ts.a < 0.0
+    }
+
     // Element shouldn't have any linked elements, because they also must be transformed.
-    // TODO: process 'fill', 'stroke' and 'filter' linked elements only if they
-    //       used only by this element.
     pub fn is_valid_attrs(node: &Node) -> bool {
         let attrs = node.attributes();
 
@@ -85,6 +98,110 @@ pub mod utils {
         true
     }
 
+    // Same as 'is_valid_attrs', but allows gradients and clip paths, that can be
+    // transformed together with the element via 'transform_linked_elements'.
+    //
+    // TODO: process 'filter' and 'mask'
+    // TODO: clone gradients that used by multiple elements
+    pub fn is_valid_linked_attrs(node: &Node, ts: &Transform) -> bool {
+        let attrs = node.attributes();
+
+        for aid in &[AId::Fill, AId::Stroke] {
+            if let Some(&AttributeValue::FuncLink(ref link)) = attrs.get_value(*aid) {
+                if !::task::is_gradient(link) || !is_valid_link(node, link, ts) {
+                    return false;
+                }
+            } else if !attrs.contains(*aid) {
+                // Inherited gradients are resolved in the user space of the current element,
+                // so they will be affected by the transform too.
+                if let Some(p) = node.parents().find(|n| n.has_attribute(*aid)) {
+                    if let Some(AttributeValue::FuncLink(_)) = p.attribute_value(*aid) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        if let Some(&AttributeValue::FuncLink(_)) = attrs.get_value(AId::Filter) {
+            return false;
+        }
+
+        if attrs.contains(AId::Mask) {
+            return false;
+        }
+
+        if let Some(&AttributeValue::FuncLink(ref link)) = attrs.get_value(AId::ClipPath) {
+            // nested clip paths are not supported
+            if !is_valid_link(node, link, ts) || link.has_attribute(AId::ClipPath) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn is_valid_link(node: &Node, link: &Node, ts: &Transform) -> bool {
+        // linked element should be used only by this element,
+        // otherwise we will break other elements
+        if !link.linked_nodes().all(|n| n == *node) {
+            return false;
+        }
+
+        // The bounding box is transformed together with the element, so elements
+        // with 'objectBoundingBox' units are not affected by the transform.
+        // But it's true only when the bounding box is not rotated.
+        if is_object_bbox_units(link) {
+            return !has_rotation(ts);
+        }
+
+        true
+    }
+
+    fn is_object_bbox_units(link: &Node) -> bool {
+        // 'gradientUnits' are resolved and 'clipPathUnits' default is 'userSpaceOnUse'
+        if link.is_tag_name(EId::ClipPath) {
+            link.has_attribute_with_value(AId::ClipPathUnits, ValueId::ObjectBoundingBox)
+        } else {
+            link.has_attribute_with_value(AId::GradientUnits, ValueId::ObjectBoundingBox)
+        }
+    }
+
+    // Applies the element's transform to the linked gradients and clip paths.
+    //
+    // Must be called only for elements that passed the 'is_valid_linked_attrs' check.
+    pub fn transform_linked_elements(node: &Node, ts: &Transform) {
+        let mut links: Vec<Node> = Vec::new();
+
+        for aid in &[AId::Fill, AId::Stroke, AId::ClipPath] {
+            if let Some(AttributeValue::FuncLink(link)) = node.attribute_value(*aid) {
+                // 'fill' and 'stroke' can reference the same gradient
+                if !links.contains(&link) {
+                    links.push(link);
+                }
+            }
+        }
+
+        for link in links {
+            if is_object_bbox_units(&link) {
+                continue;
+            }
+
+            let ts_aid = if link.is_tag_name(EId::ClipPath) {
+                AId::Transform
+            } else {
+                AId::GradientTransform
+            };
+
+            // we should prepend the element's transform
+            let mut new_ts = *ts;
+            if let Some(AttributeValue::Transform(link_ts)) = link.attribute_value(ts_aid) {
+                new_ts.append(&link_ts);
+            }
+
+            link.set_attribute(ts_aid, new_ts);
+        }
+    }
+
     // We can process only coordinates without units.
     pub fn is_valid_coords(node: &Node) -> bool {
         match node.tag_id().unwrap() {
@@ -136,6 +253,159 @@ pub mod utils {
         }
     }
 
+    // Applies the transform to the absolute path.
+    //
+    // Transform must contain only translate, rotate and proportional scale parts.
+    pub fn transform_path(path: &mut Path, ts: &Transform) {
+        debug_assert!(is_valid_rotation(ts));
+
+        let scale = (ts.a * ts.a + ts.b * ts.b).sqrt();
+        let angle = ts.b.atan2(ts.a).to_degrees();
+        let is_rotated = has_rotation(ts);
+
+        // 'HorizontalLineTo' and 'VerticalLineTo' depends on the previous segment,
+        // so we have to remember the current point
+        let mut prev_x = 0.0;
+        let mut prev_y = 0.0;
+        let mut start_x = 0.0;
+        let mut start_y = 0.0;
+
+        for seg in &mut path.d {
+            let new_seg = match *seg.data() {
+                SegmentData::MoveTo { x, y } => {
+                    prev_x = x;
+                    prev_y = y;
+                    start_x = x;
+                    start_y = y;
+
+                    let (x, y) = ts.apply(x, y);
+                    Segment::new_move_to(x, y)
+                }
+                SegmentData::LineTo { x, y } => {
+                    prev_x = x;
+                    prev_y = y;
+
+                    let (x, y) = ts.apply(x, y);
+                    Segment::new_line_to(x, y)
+                }
+                SegmentData::HorizontalLineTo { x } => {
+                    prev_x = x;
+
+                    let (x, y) = ts.apply(x, prev_y);
+                    // rotated horizontal line is not horizontal anymore
+                    if is_rotated {
+                        Segment::new_line_to(x, y)
+                    } else {
+                        Segment::new_hline_to(x)
+                    }
+                }
+                SegmentData::VerticalLineTo { y } => {
+                    prev_y = y;
+
+                    let (x, y) = ts.apply(prev_x, y);
+                    if is_rotated {
+                        Segment::new_line_to(x, y)
+                    } else {
+                        Segment::new_vline_to(y)
+                    }
+                }
+                SegmentData::CurveTo { x1, y1, x2, y2, x, y } => {
+                    prev_x = x;
+                    prev_y = y;
+
+                    let (x1, y1) = ts.apply(x1, y1);
+                    let (x2, y2) = ts.apply(x2, y2);
+                    let (x, y) = ts.apply(x, y);
+                    Segment::new_curve_to(x1, y1, x2, y2, x, y)
+                }
+                SegmentData::SmoothCurveTo { x2, y2, x, y } => {
+                    prev_x = x;
+                    prev_y = y;
+
+                    let (x2, y2) = ts.apply(x2, y2);
+                    let (x, y) = ts.apply(x, y);
+                    Segment::new_smooth_curve_to(x2, y2, x, y)
+                }
+                SegmentData::Quadratic { x1, y1, x, y } => {
+                    prev_x = x;
+                    prev_y = y;
+
+                    let (x1, y1) = ts.apply(x1, y1);
+                    let (x, y) = ts.apply(x, y);
+                    Segment::new_quad_to(x1, y1, x, y)
+                }
+                SegmentData::SmoothQuadratic { x, y } => {
+                    prev_x = x;
+                    prev_y = y;
+
+                    let (x, y) = ts.apply(x, y);
+                    Segment::new_smooth_quad_to(x, y)
+                }
+                SegmentData::EllipticalArc { rx, ry, x_axis_rotation, large_arc, sweep, x, y } => {
+                    prev_x = x;
+                    prev_y = y;
+
+                    let (x, y) = ts.apply(x, y);
+                    Segment::new_arc_to(rx * scale, ry * scale, x_axis_rotation + angle,
+                                        large_arc, sweep, x, y)
+                }
+                SegmentData::ClosePath => {
+                    prev_x = start_x;
+                    prev_y = start_y;
+
+                    Segment::new_close_path()
+                }
+            };
+
+            *seg = new_seg;
+        }
+    }
+
+    // Checks that the transform can be applied directly to the path data.
+    pub fn can_bake_transform(node: &Node, ts: &Transform) -> bool {
+        if !node.is_tag_name(EId::Path) || node.has_attribute(AId::Transform) {
+            return false;
+        }
+
+        if !is_valid_rotation(ts) {
+            return false;
+        }
+
+        // markers with 'userSpaceOnUse' units will not be scaled
+        if node.has_attributes(&[AId::Marker, AId::MarkerStart, AId::MarkerMid, AId::MarkerEnd]) {
+            return false;
+        }
+
+        // dashes will not be scaled
+        if ts.has_scale() {
+            if    node.has_attribute(AId::StrokeDasharray)
+               || node.parents().any(|n| n.has_attribute(AId::StrokeDasharray)) {
+                return false;
+            }
+        }
+
+        is_valid_linked_attrs(node, ts)
+    }
+
+    // Must be called only for elements that passed the 'can_bake_transform' check.
+    pub fn bake_transform(node: &Node, ts: &Transform) {
+        transform_linked_elements(node, ts);
+
+        {
+            let mut attrs = node.attributes_mut();
+            if let Some(&mut AttributeValue::Path(ref mut path)) = attrs.get_value_mut(AId::D) {
+                path.conv_to_absolute();
+                transform_path(path, ts);
+            }
+        }
+
+        if ts.has_scale() {
+            // we must update 'stroke-width' if transform had scale part in it
+            let (sx, _) = ts.get_scale();
+            ::task::utils::recalc_stroke_width(node, sx);
+        }
+    }
+
     pub fn get_ts(node: &Node) -> Transform {
         *node.attribute_value(AId::Transform).unwrap().as_transform().unwrap()
     }
@@ -20,13 +20,16 @@
 **
 ****************************************************************************/
 
-use task::short::{EId, AId};
+use task::short::{EId, AId, Unit};
 use super::utils;
 
 use svgdom::{Document, Node, Attributes};
-use svgdom::types::Transform;
+use svgdom::types::{Transform, Length};
+use svgdom::types::path;
 
-pub fn apply_transform_to_shapes(doc: &Document) {
+use options::Options;
+
+pub fn apply_transform_to_shapes(doc: &Document, options: &Options) {
     // If group has transform and contains only valid shapes
     // we can apply the group's transform to children before applying transform to
     // actual shape's coordinates.
@@ -36,10 +39,12 @@ pub fn apply_transform_to_shapes(doc: &Document) {
                                                   && n.has_attribute(AId::Transform));
 
     for node in iter {
-        if !utils::has_valid_transform(&node) || !utils::is_valid_attrs(&node) {
+        if !utils::is_valid_attrs(&node) {
             continue;
         }
 
+        let ts = utils::get_ts(&node);
+
         // check that all children is valid
         let is_valid = node.children().svg().all(|n| {
             let flag = match n.tag_id().unwrap() {
@@ -50,15 +55,22 @@ pub fn apply_transform_to_shapes(doc: &Document) {
                 _ => false,
             };
 
-               flag
-            && utils::has_valid_transform(&n)
-            && utils::is_valid_attrs(&n)
+            if !flag {
+                return false;
+            }
+
+            // check the resulting transform and not the group's one
+            let mut ts1 = ts;
+            if n.has_attribute(AId::Transform) {
+                ts1.append(&utils::get_ts(&n));
+            }
+
+               is_valid_shape(&n, &ts1, options)
+            && utils::is_valid_linked_attrs(&n, &ts1)
             && utils::is_valid_coords(&n)
         });
 
         if is_valid {
-            let ts = utils::get_ts(&node);
-
             // apply group's transform to children
             for child in node.children().svg() {
                 if child.has_attribute(AId::Transform) {
@@ -82,8 +94,20 @@ pub fn apply_transform_to_shapes(doc: &Document) {
     // apply transform to shapes
     let iter = doc.descendants().svg().filter(|n| n.has_attribute(AId::Transform));
     for node in iter {
+        let ts = utils::get_ts(&node);
+
+        if !is_valid_shape(&node, &ts, options) {
+            continue;
+        }
+
         match node.tag_id().unwrap() {
-            EId::Rect => process_rect(&node),
+            EId::Rect => {
+                if utils::has_rotation(&ts) {
+                    process_rotated_rect(&node);
+                } else {
+                    process_rect(&node);
+                }
+            }
             EId::Circle => process_circle(&node),
             EId::Ellipse => process_ellipse(&node),
             EId::Line => process_line(&node),
@@ -92,16 +116,63 @@ pub fn apply_transform_to_shapes(doc: &Document) {
     }
 }
 
+// Checks that transform can be applied to the shape.
+fn is_valid_shape(node: &Node, ts: &Transform, options: &Options) -> bool {
+    if !utils::has_rotation(ts) {
+        return utils::is_valid_transform(ts);
+    }
+
+    if !utils::is_valid_rotation(ts) {
+        return false;
+    }
+
+    let attrs = node.attributes();
+    match node.tag_id().unwrap() {
+        // rotated circle is still a circle, we only need to move the center
+          EId::Circle
+        | EId::Line => true,
+        EId::Ellipse => {
+            // only ellipses that are actually circles
+            let rx = get_value!(attrs, Length, AId::Rx, Length::zero());
+            let ry = get_value!(attrs, Length, AId::Ry, Length::zero());
+            rx == ry
+        }
+        EId::Rect => {
+            // Rotated rect can't be represented as 'rect' element,
+            // so we can only convert it to path.
+            if !options.convert_shapes {
+                return false;
+            }
+
+            // we convert only simple rects, not rounded
+            let rx = get_value!(attrs, Length, AId::Rx, Length::zero());
+            let ry = get_value!(attrs, Length, AId::Ry, Length::zero());
+            if rx.num != 0.0 || ry.num != 0.0 {
+                return false;
+            }
+
+            let w = get_value!(attrs, Length, AId::Width, Length::zero());
+            let h = get_value!(attrs, Length, AId::Height, Length::zero());
+
+            // zero-sized rects will be removed by 'remove_invisible_elements'
+               w.num != 0.0 && h.num != 0.0
+            && w.unit == Unit::None && h.unit == Unit::None
+        }
+        _ => false,
+    }
+}
+
 fn process<F>(node: &Node, func: F)
     where F : Fn(&mut Attributes, &Transform)
 {
-    if    !utils::has_valid_transform(node)
-       || !utils::is_valid_attrs(node)
+    let ts = utils::get_ts(node);
+
+    if    !utils::is_valid_linked_attrs(node, &ts)
        || !utils::is_valid_coords(node) {
         return;
     }
 
-    let ts = utils::get_ts(node);
+    utils::transform_linked_elements(node, &ts);
 
     {
         let mut attrs = node.attributes_mut();
@@ -162,11 +233,57 @@ fn process_line(node: &Node) {
     });
 }
 
+// Rotated rect can't be represented as 'rect' element, so we convert it to a path.
+fn process_rotated_rect(node: &Node) {
+    let ts = utils::get_ts(node);
+
+    if    !utils::is_valid_linked_attrs(node, &ts)
+       || !utils::is_valid_coords(node) {
+        return;
+    }
+
+    utils::transform_linked_elements(node, &ts);
+
+    let path = {
+        let attrs = node.attributes();
+
+        let x = get_value!(attrs, Length, AId::X, Length::zero()).num;
+        let y = get_value!(attrs, Length, AId::Y, Length::zero()).num;
+        let w = get_value!(attrs, Length, AId::Width, Length::zero()).num;
+        let h = get_value!(attrs, Length, AId::Height, Length::zero()).num;
+
+        let (x1, y1) = ts.apply(x, y);
+        let (x2, y2) = ts.apply(x + w, y);
+        let (x3, y3) = ts.apply(x + w, y + h);
+        let (x4, y4) = ts.apply(x, y + h);
+
+        path::Builder::new()
+            .move_to(x1, y1)
+            .line_to(x2, y2)
+            .line_to(x3, y3)
+            .line_to(x4, y4)
+            .close_path()
+            .finalize()
+    };
+
+    node.set_attribute(AId::D, path);
+    node.set_tag_name(EId::Path);
+    node.remove_attributes(&[AId::X, AId::Y, AId::Rx, AId::Ry, AId::Width, AId::Height,
+                             AId::Transform]);
+
+    if ts.has_scale() {
+        // we must update 'stroke-width' if transform had scale part in it
+        let (sx, _) = ts.get_scale();
+        ::task::utils::recalc_stroke_width(node, sx);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use svgdom::{Document, WriteToString};
     use task::utils;
+    use options::Options;
 
     macro_rules! test {
         ($name:ident, $in_text:expr, $out_text:expr) => (
@@ -174,7 +291,9 @@ mod tests {
             fn $name() {
                 let doc = Document::from_data($in_text).unwrap();
                 utils::resolve_gradient_attributes(&doc).unwrap();
-                apply_transform_to_shapes(&doc);
+                let mut options = Options::default();
+                options.convert_shapes = true;
+                apply_transform_to_shapes(&doc, &options);
                 assert_eq_text!(doc.to_string_with_opt(&write_opt_for_tests!()), $out_text);
             }
         )
@@ -289,4 +408,141 @@ b"<svg>
 "
 );
 
+    test!(apply_rotate_circle_1,
+b"<svg>
+    <circle cx='10' cy='0' r='5' transform='rotate(90)'/>
+</svg>",
+"<svg>
+    <circle cx='0' cy='10' r='5'/>
+</svg>
+");
+
+    test!(apply_rotate_circle_2,
+b"<svg>
+    <circle cx='10' cy='0' r='5' transform='rotate(90) scale(2)'/>
+</svg>",
+"<svg>
+    <circle cx='0' cy='20' r='10' stroke-width='2'/>
+</svg>
+");
+
+    test!(apply_rotate_line_1,
+b"<svg>
+    <line x1='10' x2='20' y1='0' y2='0' transform='rotate(90)'/>
+</svg>",
+"<svg>
+    <line x1='0' x2='0' y1='10' y2='20'/>
+</svg>
+");
+
+    test!(apply_rotate_rect_1,
+b"<svg>
+    <rect height='10' width='20' x='10' y='0' transform='rotate(90)'/>
+</svg>",
+"<svg>
+    <path d='M 0 10 L 0 30 L -10 30 L -10 10 Z'/>
+</svg>
+");
+
+    test!(apply_rotate_g_1,
+b"<svg>
+    <g transform='rotate(90)'>
+        <circle cx='10' cy='0' r='5'/>
+        <line x1='10' x2='20' y1='0' y2='0'/>
+    </g>
+</svg>",
+"<svg>
+    <g>
+        <circle cx='0' cy='10' r='5'/>
+        <line x1='0' x2='0' y1='10' y2='20'/>
+    </g>
+</svg>
+");
+
+    // rotated ellipse and rounded rect can't be processed
+    test_eq!(keep_rotate_1,
+b"<svg>
+    <ellipse cx='10' cy='10' rx='5' ry='10' transform='matrix(0.70710678 0.70710678 -0.70710678 0.70710678 0 0)'/>
+    <rect height='10' rx='2' transform='matrix(0.70710678 0.70710678 -0.70710678 0.70710678 0 0)' width='10'/>
+</svg>
+"
+);
+
+    #[test]
+    fn keep_rotate_rect_1() {
+        // rotated rect can't be processed without shapes converting
+        let text = "<svg>
+    <rect height='10' transform='matrix(0.70710678 0.70710678 -0.70710678 0.70710678 0 0)' width='10'/>
+</svg>
+";
+        let doc = Document::from_data(text.as_bytes()).unwrap();
+        apply_transform_to_shapes(&doc, &Options::default());
+        assert_eq_text!(doc.to_string_with_opt(&write_opt_for_tests!()), text);
+    }
+
+    test!(apply_with_gradient_1,
+b"<svg>
+    <linearGradient id='lg1' gradientUnits='userSpaceOnUse'/>
+    <rect fill='url(#lg1)' height='10' width='10' transform='translate(10 20)'/>
+</svg>",
+"<svg>
+    <linearGradient id='lg1' gradientTransform='translate(10 20)' gradientUnits='userSpaceOnUse'/>
+    <rect fill='url(#lg1)' height='10' width='10' x='10' y='20'/>
+</svg>
+");
+
+    test!(apply_with_gradient_2,
+b"<svg>
+    <linearGradient id='lg1' gradientTransform='scale(2)' gradientUnits='userSpaceOnUse'/>
+    <rect fill='url(#lg1)' height='10' stroke='url(#lg1)' width='10' transform='translate(10 20)'/>
+</svg>",
+"<svg>
+    <linearGradient id='lg1' gradientTransform='matrix(2 0 0 2 10 20)' gradientUnits='userSpaceOnUse'/>
+    <rect fill='url(#lg1)' height='10' stroke='url(#lg1)' width='10' x='10' y='20'/>
+</svg>
+");
+
+    // 'objectBoundingBox' units are not affected by translate and scale
+    test!(apply_with_gradient_3,
+b"<svg>
+    <linearGradient id='lg1'/>
+    <rect fill='url(#lg1)' height='10' width='10' transform='translate(10 20)'/>
+</svg>",
+"<svg>
+    <linearGradient id='lg1'/>
+    <rect fill='url(#lg1)' height='10' width='10' x='10' y='20'/>
+</svg>
+");
+
+    test!(apply_with_clip_path_1,
+b"<svg>
+    <clipPath id='cp1'>
+        <rect height='5' width='5'/>
+    </clipPath>
+    <rect clip-path='url(#cp1)' height='10' width='10' transform='translate(10 20)'/>
+</svg>",
+"<svg>
+    <clipPath id='cp1' transform='translate(10 20)'>
+        <rect height='5' width='5'/>
+    </clipPath>
+    <rect clip-path='url(#cp1)' height='10' width='10' x='10' y='20'/>
+</svg>
+");
+
+    // gradient is used by multiple elements
+    test_eq!(keep_with_gradient_1,
+b"<svg>
+    <linearGradient id='lg1' gradientUnits='userSpaceOnUse'/>
+    <rect fill='url(#lg1)' height='10' transform='translate(10 20)' width='10'/>
+    <rect fill='url(#lg1)' height='10' width='10'/>
+</svg>
+");
+
+    // 'objectBoundingBox' units are affected by rotation
+    test_eq!(keep_with_gradient_2,
+b"<svg>
+    <linearGradient id='lg1'/>
+    <circle cx='10' cy='10' fill='url(#lg1)' r='5' transform='matrix(0.70710678 0.70710678 -0.70710678 0.70710678 0 0)'/>
+</svg>
+");
 }
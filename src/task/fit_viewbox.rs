@@ -0,0 +1,328 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+use super::short::{EId, AId, Unit};
+use super::rm_offscreen_elements::{has_text, calc_viewport};
+use super::ungroup_svg::has_percent_values;
+
+use geom;
+
+use svgdom::{Document, Node, AttributeValue, ValueId};
+use svgdom::types::{Length, Transform};
+
+// Sets the root 'viewBox' to the content bounding box.
+//
+// The content is translated to start at the padding position
+// and 'width'/'height' are updated to keep the content scale.
+pub fn fit_viewbox(doc: &Document, padding: f64) {
+    // doc must contain 'svg' node, so we can safely unwrap
+    let svg = doc.svg_element().unwrap();
+
+    // the text bbox is only an approximation
+    if has_text(&svg) {
+        return;
+    }
+
+    // percentage values are resolved relative to the 'viewBox'
+    if svg.descendants().svg().any(|n| n != svg && has_percent_values(&n)) {
+        return;
+    }
+
+    let viewport = calc_viewport(&svg);
+
+    let mut bbox: Option<geom::Rect> = None;
+    for child in svg.children().svg() {
+        if child.has_attribute_with_value(AId::Display, ValueId::None) {
+            continue;
+        }
+
+        // nested 'svg' elements establish a new viewport
+        if child.is_tag_name(EId::Svg) {
+            return;
+        }
+
+        if !is_graphic_element(&child) {
+            continue;
+        }
+
+        let r = match geom::calc_transformed_bbox(&child, true) {
+            Some(Some(r)) => r,
+            Some(None) => continue,
+            None => return,
+        };
+
+        // the content outside the current viewport is not visible,
+        // so it should not stretch the new 'viewBox'
+        let r = match viewport {
+            Some(ref vp) => {
+                match r.intersection(vp) {
+                    Some(r) => r,
+                    None => continue,
+                }
+            }
+            None => r,
+        };
+
+        bbox = Some(match bbox {
+            Some(b) => b.union(&r),
+            None => r,
+        });
+    }
+
+    let bbox = match bbox {
+        Some(r) => r,
+        None => return,
+    };
+
+    let w = bbox.width() + padding * 2.0;
+    let h = bbox.height() + padding * 2.0;
+    if !(w > 0.0 && h > 0.0) {
+        return;
+    }
+
+    let (new_width, new_height) = match calc_new_size(&svg, w, h) {
+        Some(v) => v,
+        None => return,
+    };
+
+    let dx = padding - bbox.x1;
+    let dy = padding - bbox.y1;
+    if dx != 0.0 || dy != 0.0 {
+        transform_content(doc, &svg, Transform::new(1.0, 0.0, 0.0, 1.0, dx, dy));
+    }
+
+    svg.set_attribute(AId::ViewBox, AttributeValue::NumberList(vec![0.0, 0.0, w, h]));
+
+    if let Some(len) = new_width {
+        svg.set_attribute(AId::Width, len);
+    }
+
+    if let Some(len) = new_height {
+        svg.set_attribute(AId::Height, len);
+    }
+}
+
+// Calculates 'width' and 'height' values for the new 'viewBox',
+// which will preserve the current content scale.
+//
+// `None` in the result means that the attribute should not be changed.
+fn calc_new_size(svg: &Node, w: f64, h: f64) -> Option<(Option<Length>, Option<Length>)> {
+    let attrs = svg.attributes();
+
+    let get_len = |aid: AId| {
+        match attrs.get_value(aid) {
+            Some(&AttributeValue::Length(len)) if len.unit != Unit::Percent => Some(Some(len)),
+            Some(&AttributeValue::Length(_)) | None => Some(None),
+            Some(_) => None,
+        }
+    };
+
+    let width = try_opt!(get_len(AId::Width));
+    let height = try_opt!(get_len(AId::Height));
+
+    match attrs.get_value(AId::ViewBox) {
+        Some(&AttributeValue::NumberList(ref vb)) => {
+            let (width, height) = match (width, height) {
+                (Some(width), Some(height)) => (width, height),
+                // the viewport depends on the outer layout and will be filled anyway
+                (None, None) => return Some((None, None)),
+                // the content scale depends on the layout
+                _ => return None,
+            };
+
+            if width.unit != height.unit {
+                return None;
+            }
+
+            let aspect = match attrs.get_value(AId::PreserveAspectRatio) {
+                Some(&AttributeValue::String(ref s)) => Some(s.as_str()),
+                Some(_) => return None,
+                None => None,
+            };
+
+            let ts = try_opt!(::task::utils::view_box_transform(vb, aspect,
+                                                                width.num, height.num));
+
+            Some((Some(Length::new(w * ts.a, width.unit)),
+                  Some(Length::new(h * ts.d, height.unit))))
+        }
+        Some(_) => None,
+        None => {
+            // without the 'viewBox' the user unit is equal to the viewport unit
+            match (width, height) {
+                (Some(width), Some(height))
+                    if width.unit == Unit::None && height.unit == Unit::None => {
+                    Some((Some(Length::new(w, Unit::None)), Some(Length::new(h, Unit::None))))
+                }
+                _ => None,
+            }
+        }
+    }
+}
+
+// Prepends the transform to all rendered children of the root 'svg' element.
+pub fn transform_content(doc: &Document, svg: &Node, ts: Transform) {
+    let nodes: Vec<Node> = svg.children().svg().filter(is_graphic_element).collect();
+
+    if nodes.iter().any(|n| n.is_used()) {
+        // The transform of the referenced element is applied only once,
+        // so we have to use a new group.
+        let g = doc.create_element(EId::G);
+        g.set_attribute(AId::Transform, ts);
+        svg.append(&g);
+
+        for node in nodes {
+            g.append(&node);
+        }
+    } else {
+        for node in nodes {
+            let mut new_ts = ts;
+            if let Some(AttributeValue::Transform(child_ts)) = node.attribute_value(AId::Transform) {
+                new_ts.append(&child_ts);
+            }
+            node.set_attribute(AId::Transform, new_ts);
+        }
+    }
+}
+
+pub fn is_graphic_element(node: &Node) -> bool {
+    match node.tag_id().unwrap() {
+          EId::G
+        | EId::A
+        | EId::Switch
+        | EId::Path
+        | EId::Text
+        | EId::Image
+        | EId::Use => true,
+        _ => node.is_basic_shape(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::{Document, WriteToString};
+
+    macro_rules! test {
+        ($name:ident, $padding:expr, $in_text:expr, $out_text:expr) => (
+            base_test!($name, |doc: &Document| fit_viewbox(doc, $padding), $in_text, $out_text);
+        )
+    }
+
+    macro_rules! test_eq {
+        ($name:ident, $in_text:expr) => (
+            test!($name, 0.0, $in_text, String::from_utf8_lossy($in_text));
+        )
+    }
+
+    test!(fit_1, 0.0,
+b"<svg viewBox='0 0 100 100'>
+    <rect height='10' width='20' x='10' y='20'/>
+    <rect height='10' transform='translate(5 0)' width='10' x='10' y='20'/>
+</svg>",
+"<svg viewBox='0 0 20 10'>
+    <rect height='10' transform='translate(-10 -20)' width='20' x='10' y='20'/>
+    <rect height='10' transform='translate(-5 -20)' width='10' x='10' y='20'/>
+</svg>
+");
+
+    test!(fit_2, 0.0,
+b"<svg height='200' viewBox='0 0 100 100' width='200'>
+    <rect height='10' width='20'/>
+</svg>",
+"<svg height='20' viewBox='0 0 20 10' width='40'>
+    <rect height='10' width='20'/>
+</svg>
+");
+
+    test!(fit_3, 5.0,
+b"<svg height='100' width='100'>
+    <rect height='10' width='20' x='10' y='20'/>
+</svg>",
+"<svg height='20' viewBox='0 0 30 20' width='30'>
+    <rect height='10' transform='translate(-5 -15)' width='20' x='10' y='20'/>
+</svg>
+");
+
+    // the stroke should be included
+    test!(fit_4, 0.0,
+b"<svg viewBox='0 0 100 100'>
+    <circle cx='50' cy='50' r='10' stroke='#000000' stroke-width='2'/>
+</svg>",
+"<svg viewBox='0 0 22 22'>
+    <circle cx='50' cy='50' r='10' stroke='#000000' stroke-width='2' transform='translate(-39 -39)'/>
+</svg>
+");
+
+    // the content outside the viewport should be ignored
+    test!(fit_5, 0.0,
+b"<svg viewBox='0 0 100 100'>
+    <rect height='10' width='20' x='10' y='20'/>
+    <rect height='10' width='10' x='200' y='20'/>
+</svg>",
+"<svg viewBox='0 0 20 10'>
+    <rect height='10' transform='translate(-10 -20)' width='20' x='10' y='20'/>
+    <rect height='10' transform='translate(-10 -20)' width='10' x='200' y='20'/>
+</svg>
+");
+
+    test!(fit_6, 0.0,
+b"<svg viewBox='0 0 100 100'>
+    <rect height='50' width='200' x='50' y='20'/>
+</svg>",
+"<svg viewBox='0 0 50 50'>
+    <rect height='50' transform='translate(-50 -20)' width='200' x='50' y='20'/>
+</svg>
+");
+
+    test!(fit_with_use_1, 0.0,
+b"<svg viewBox='0 0 100 100' xmlns:xlink='http://www.w3.org/1999/xlink'>
+    <rect id='rect1' height='10' width='10' x='10' y='10'/>
+    <use x='10' xlink:href='#rect1'/>
+</svg>",
+"<svg viewBox='0 0 20 10' xmlns:xlink='http://www.w3.org/1999/xlink'>
+    <g transform='translate(-10 -10)'>
+        <rect id='rect1' height='10' width='10' x='10' y='10'/>
+        <use x='10' xlink:href='#rect1'/>
+    </g>
+</svg>
+");
+
+    test_eq!(keep_text_1,
+b"<svg viewBox='0 0 100 100'>
+    <text>Text</text>
+</svg>
+");
+
+    test_eq!(keep_percent_1,
+b"<svg viewBox='0 0 100 100'>
+    <rect height='10%' width='20'/>
+</svg>
+");
+
+    // the content scale depends on the layout
+    test_eq!(keep_size_1,
+b"<svg viewBox='0 0 100 100' width='200'>
+    <rect height='10' width='20'/>
+</svg>
+");
+}
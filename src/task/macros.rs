@@ -57,13 +57,3 @@ macro_rules! assert_eq_text {
         }
     })
 }
-
-macro_rules! get_value {
-    ($attrs:expr, $t:ident, $aid:expr, $def:expr) => (
-        if let Some(&AttributeValue::$t(v)) = $attrs.get_value($aid) {
-            v
-        } else {
-            $def
-        }
-    )
-}
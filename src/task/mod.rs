@@ -27,25 +27,34 @@ pub use self::final_fixes::{
     remove_empty_defs,
     fix_xmlns_attribute,
 };
+pub use self::fit_viewbox::fit_viewbox;
 pub use self::fix_attrs::fix_invalid_attributes;
 pub use self::group_by_style::group_by_style;
 pub use self::group_defs::group_defs;
 pub use self::join_style_attrs::join_style_attributes;
 pub use self::merge_gradients::merge_gradients;
+pub use self::normalize_size::normalize_size;
+pub use self::optimize_filters::optimize_filters;
 pub use self::preclean_checks::preclean_checks;
 pub use self::rm_default_attrs::remove_default_attributes;
 pub use self::rm_dupl_defs::{
     remove_dupl_linear_gradients,
     remove_dupl_radial_gradients,
     remove_dupl_fe_gaussian_blur,
+    remove_dupl_elements,
 };
+pub use self::rebase_coords::rebase_coordinates;
 pub use self::regroup_gradient_stops::regroup_gradient_stops;
+pub use self::replace_dupl::replace_dupl_with_use;
+pub use self::resolve_units::resolve_units;
 pub use self::resolve_use::resolve_use;
 pub use self::rm_elems::remove_element;
 pub use self::rm_gradient_attrs::remove_gradient_attributes;
 pub use self::rm_invalid_stops::remove_invalid_stops;
 pub use self::rm_invisible_elements::remove_invisible_elements;
 pub use self::rm_needless_attrs::remove_needless_attributes;
+pub use self::rm_occluded_elements::remove_occluded_elements;
+pub use self::rm_offscreen_elements::remove_offscreen_elements;
 pub use self::rm_text_attrs::remove_text_attributes;
 pub use self::rm_unref_ids::remove_unreferenced_ids;
 pub use self::rm_unused_coords::remove_unused_coordinates;
@@ -54,18 +63,25 @@ pub use self::rm_version::remove_version;
 pub use self::trim_ids::trim_ids;
 pub use self::ungroup_defs::ungroup_defs;
 pub use self::ungroup_groups::ungroup_groups;
+pub use self::ungroup_svg::ungroup_nested_svg;
 
 #[macro_use]
 mod macros;
 mod conv_shapes;
 mod final_fixes;
+mod fit_viewbox;
 mod fix_attrs;
 mod group_by_style;
 mod group_defs;
 mod join_style_attrs;
 mod merge_gradients;
+mod normalize_size;
+mod optimize_filters;
 mod preclean_checks;
+mod rebase_coords;
 mod regroup_gradient_stops;
+mod replace_dupl;
+mod resolve_units;
 mod resolve_use;
 mod rm_default_attrs;
 mod rm_dupl_defs;
@@ -74,6 +90,8 @@ mod rm_gradient_attrs;
 mod rm_invalid_stops;
 mod rm_invisible_elements;
 mod rm_needless_attrs;
+mod rm_occluded_elements;
+mod rm_offscreen_elements;
 mod rm_text_attrs;
 mod rm_unref_ids;
 mod rm_unused_coords;
@@ -82,6 +100,7 @@ mod rm_version;
 mod trim_ids;
 mod ungroup_defs;
 mod ungroup_groups;
+mod ungroup_svg;
 pub mod apply_transforms;
 pub mod paths;
 
@@ -94,7 +113,8 @@ mod short {
 
 pub mod utils {
     use svgdom::{Document, Node, AttributeValue};
-    use task::short::AId;
+    use svgdom::types::Transform;
+    use task::short::{EId, AId};
     use svgdom_utils;
 
     pub fn resolve_gradient_attributes(doc: &Document) -> Result<(), svgdom_utils::Error> {
@@ -124,4 +144,117 @@ pub mod utils {
 
         node.set_attribute(AId::StrokeWidth, stroke_width);
     }
+
+    /// Returns the first 'defs' element of the root 'svg' element.
+    ///
+    /// Creates a new one if it doesn't exist.
+    pub fn get_defs(doc: &Document) -> Node {
+        // doc must contain 'svg' node, so we can safely unwrap
+        let svg = doc.svg_element().unwrap();
+        match svg.children().find(|n| n.is_tag_name(EId::Defs)) {
+            Some(n) => n,
+            None => {
+                let defs = doc.create_element(EId::Defs);
+                svg.prepend(&defs);
+                defs
+            }
+        }
+    }
+
+    /// Generates a new unique element ID with the specified prefix.
+    pub fn gen_id(doc: &Document, prefix: &str) -> String {
+        let mut n = 1;
+
+        let mut s = String::new();
+        loop {
+            s.clear();
+            s.push_str(prefix);
+            s.push_str(&n.to_string());
+
+            if !doc.descendants().svg().any(|n| *n.id() == s) {
+                break;
+            }
+
+            n += 1;
+        }
+
+        s
+    }
+
+    /// Returns a transform which maps the `viewBox` to the viewport with the specified size.
+    ///
+    /// `view_box` is `[x, y, width, height]`.
+    /// `aspect` is a `preserveAspectRatio` attribute value, if any.
+    ///
+    /// Returns `None` if `viewBox` or `preserveAspectRatio` are invalid.
+    pub fn view_box_transform(view_box: &[f64], aspect: Option<&str>,
+                              width: f64, height: f64) -> Option<Transform> {
+        if view_box.len() != 4 || !(view_box[2] > 0.0 && view_box[3] > 0.0) {
+            return None;
+        }
+
+        let (vb_x, vb_y, vb_w, vb_h) = (view_box[0], view_box[1], view_box[2], view_box[3]);
+
+        let mut align = "xMidYMid";
+        let mut slice = false;
+        if let Some(aspect) = aspect {
+            let mut iter = aspect.split_whitespace().peekable();
+
+            // 'defer' has meaning only for 'image' elements
+            if iter.peek() == Some(&"defer") {
+                iter.next();
+            }
+
+            if let Some(a) = iter.next() {
+                align = a;
+            }
+
+            match iter.next() {
+                Some("meet") | None => {}
+                Some("slice") => slice = true,
+                _ => return None,
+            }
+
+            if iter.next().is_some() {
+                return None;
+            }
+        }
+
+        let mut sx = width / vb_w;
+        let mut sy = height / vb_h;
+
+        if align == "none" {
+            return Some(Transform::new(sx, 0.0, 0.0, sy, -vb_x * sx, -vb_y * sy));
+        }
+
+        if align.len() != 8 || !align.is_ascii() {
+            return None;
+        }
+
+        let s = if slice { sx.max(sy) } else { sx.min(sy) };
+        sx = s;
+        sy = s;
+
+        let mut tx = -vb_x * sx;
+        let mut ty = -vb_y * sy;
+
+        let dx = width - vb_w * sx;
+        let dy = height - vb_h * sy;
+
+        match &align[0..4] {
+            "xMin" => {}
+            "xMid" => tx += dx / 2.0,
+            "xMax" => tx += dx,
+            _ => return None,
+        }
+
+        match &align[4..8] {
+            "YMin" => {}
+            "YMid" => ty += dy / 2.0,
+            "YMax" => ty += dy,
+            _ => return None,
+        }
+
+        Some(Transform::new(sx, 0.0, 0.0, sy, tx, ty))
+    }
 }
@@ -0,0 +1,186 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+use super::short::{EId, AId, Unit};
+use super::fit_viewbox::transform_content;
+use super::ungroup_svg::has_percent_values;
+
+use geom::{self, Rect};
+
+use svgdom::{Document, Node, AttributeValue};
+use svgdom::types::{Length, Transform};
+
+// Scales and centers the content, so the root 'viewBox' became '0 0 size size'.
+//
+// When `preserve_stroke_width` is set, stroke widths will have the same values
+// in the new coordinates.
+pub fn normalize_size(doc: &Document, size: f64, preserve_stroke_width: bool) {
+    // doc must contain 'svg' node, so we can safely unwrap
+    let svg = doc.svg_element().unwrap();
+
+    // percentage values are resolved relative to the 'viewBox'
+    if svg.descendants().svg().any(|n| n != svg && has_percent_values(&n)) {
+        return;
+    }
+
+    let vb = match get_view_box(&svg) {
+        Some(r) => r,
+        None => return,
+    };
+
+    // preserve the aspect ratio
+    let scale = size / vb.width().max(vb.height());
+    let dx = (size - vb.width() * scale) / 2.0 - vb.x1 * scale;
+    let dy = (size - vb.height() * scale) / 2.0 - vb.y1 * scale;
+
+    if preserve_stroke_width && scale != 1.0 {
+        compensate_stroke_width(&svg, scale);
+    }
+
+    let ts = Transform::new(scale, 0.0, 0.0, scale, dx, dy);
+    if !ts.is_default() {
+        transform_content(doc, &svg, ts);
+    }
+
+    svg.set_attribute(AId::ViewBox, AttributeValue::NumberList(vec![0.0, 0.0, size, size]));
+
+    for aid in &[AId::Width, AId::Height] {
+        if let Some(AttributeValue::Length(len)) = svg.attribute_value(*aid) {
+            if len.unit != Unit::Percent {
+                svg.set_attribute(*aid, Length::new(size, Unit::None));
+            }
+        }
+    }
+}
+
+// Returns the 'viewBox' or the viewport rect if the 'viewBox' is not set.
+fn get_view_box(svg: &Node) -> Option<Rect> {
+    let attrs = svg.attributes();
+
+    match attrs.get_value(AId::ViewBox) {
+        Some(&AttributeValue::NumberList(ref vb)) => {
+            if vb.len() == 4 && vb[2] > 0.0 && vb[3] > 0.0 {
+                Some(Rect::new(vb[0], vb[1], vb[2], vb[3]))
+            } else {
+                None
+            }
+        }
+        Some(_) => None,
+        None => {
+            match (attrs.get_value(AId::Width), attrs.get_value(AId::Height)) {
+                (Some(&AttributeValue::Length(w)), Some(&AttributeValue::Length(h))) => {
+                    if    w.unit == Unit::None && h.unit == Unit::None
+                       && w.num > 0.0 && h.num > 0.0 {
+                        Some(Rect::new(0.0, 0.0, w.num, h.num))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            }
+        }
+    }
+}
+
+fn compensate_stroke_width(svg: &Node, scale: f64) {
+    for node in svg.descendants().svg() {
+        if !(node.is_basic_shape() || node.is_tag_name(EId::Path) || node.is_tag_name(EId::Text)) {
+            continue;
+        }
+
+        // clip paths don't use the stroke and the marker content
+        // is already scaled by the stroke width
+        if node.parents().any(|n| n.is_tag_name(EId::ClipPath) || n.is_tag_name(EId::Marker)) {
+            continue;
+        }
+
+        match geom::resolve_stroke_width(&node) {
+            Some(sw) if sw > 0.0 => ::task::utils::recalc_stroke_width(&node, 1.0 / scale),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::{Document, WriteToString};
+
+    macro_rules! test {
+        ($name:ident, $preserve:expr, $in_text:expr, $out_text:expr) => (
+            base_test!($name, |doc: &Document| normalize_size(doc, 24.0, $preserve),
+                       $in_text, $out_text);
+        )
+    }
+
+    test!(scale_1, false,
+b"<svg viewBox='0 0 48 48'>
+    <rect height='10' width='10'/>
+</svg>",
+"<svg viewBox='0 0 24 24'>
+    <rect height='10' transform='scale(0.5)' width='10'/>
+</svg>
+");
+
+    // the content should be centered
+    test!(scale_2, false,
+b"<svg height='100' viewBox='10 10 48 24' width='200'>
+    <rect height='10' width='10' x='10' y='10'/>
+</svg>",
+"<svg height='24' viewBox='0 0 24 24' width='24'>
+    <rect height='10' transform='matrix(0.5 0 0 0.5 -5 1)' width='10' x='10' y='10'/>
+</svg>
+");
+
+    test!(no_view_box_1, false,
+b"<svg height='12' width='12'>
+    <rect height='10' width='10'/>
+</svg>",
+"<svg height='24' viewBox='0 0 24 24' width='24'>
+    <rect height='10' transform='scale(2)' width='10'/>
+</svg>
+");
+
+    test!(preserve_stroke_width_1, true,
+b"<svg viewBox='0 0 48 48'>
+    <g stroke='#000000' stroke-width='2'>
+        <rect height='10' width='10'/>
+    </g>
+    <rect height='10' width='10'/>
+</svg>",
+"<svg viewBox='0 0 24 24'>
+    <g stroke='#000000' stroke-width='2' transform='scale(0.5)'>
+        <rect height='10' stroke-width='4' width='10'/>
+    </g>
+    <rect height='10' transform='scale(0.5)' width='10'/>
+</svg>
+");
+
+    test!(keep_1, false,
+b"<svg viewBox='0 0 24 24'>
+    <rect height='10' width='10'/>
+</svg>",
+"<svg viewBox='0 0 24 24'>
+    <rect height='10' width='10'/>
+</svg>
+");
+}
@@ -0,0 +1,533 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+use super::short::{EId, AId, Unit};
+use geom;
+
+use svgdom::{Document, Node, AttributeValue, ValueId, WriteOptions, WriteBuffer};
+use svgdom::types::Length;
+
+pub fn optimize_filters(doc: &Document, opt: &WriteOptions) {
+    let filters: Vec<Node> = doc.descendants().svg()
+        .filter(|n| n.is_tag_name(EId::Filter))
+        .collect();
+
+    for filter in filters {
+        // filters that are linked to other filters can inherit primitives and attributes
+        if    filter.has_attribute(AId::XlinkHref)
+           || filter.linked_nodes().any(|n| n.is_tag_name(EId::Filter)) {
+            continue;
+        }
+
+        rm_passthrough_primitives(&filter);
+        rm_implicit_inputs(&filter);
+        rm_unused_results(&filter);
+        process_filter_region(&filter, opt);
+        process_primitive_units(&filter);
+    }
+}
+
+// Removes filter primitives that return their input image without any changes.
+fn rm_passthrough_primitives(filter: &Node) {
+    let mut i = 0;
+    loop {
+        let prims: Vec<Node> = filter.children().svg().collect();
+
+        // a filter without children disables the element rendering
+        if i >= prims.len() || prims.len() == 1 {
+            break;
+        }
+
+        if is_passthrough(&prims[i]) && rm_passthrough(&prims, i) {
+            // do not increment the index, because the current primitive is removed
+            continue;
+        }
+
+        i += 1;
+    }
+}
+
+fn is_passthrough(node: &Node) -> bool {
+    // primitive subregion clips the input image
+    if node.has_attributes(&[AId::X, AId::Y, AId::Width, AId::Height]) {
+        return false;
+    }
+
+    let attrs = node.attributes();
+
+    match node.tag_id().unwrap() {
+        EId::FeOffset => {
+            is_zero(attrs.get_value(AId::Dx)) && is_zero(attrs.get_value(AId::Dy))
+        }
+        EId::FeGaussianBlur => {
+            // 'A value of zero disables the effect of the given filter primitive
+            // (i.e., the result is the filter input image).'
+            is_zero(attrs.get_value(AId::StdDeviation))
+        }
+        EId::FeComponentTransfer => {
+            // transfer function elements without 'type' are invalid,
+            // so we are checking only the 'identity' type
+            node.children().svg().all(|n| {
+                value_to_string(n.attribute_value(AId::Type).as_ref())
+                    == Some("identity".to_string())
+            })
+        }
+        _ => false,
+    }
+}
+
+// Checks that a number or a list of numbers contains only zeros.
+//
+// Not set attributes are zero by default.
+fn is_zero(value: Option<&AttributeValue>) -> bool {
+    match value_to_string(value) {
+        Some(s) => {
+            s.split(|c: char| c == ' ' || c == ',')
+             .filter(|s| !s.is_empty())
+             .all(|s| s.parse::<f64>() == Ok(0.0))
+        }
+        None => true,
+    }
+}
+
+// Removes the primitive and redirects its consumers to the primitive input.
+//
+// Returns `false` if the primitive can't be removed.
+fn rm_passthrough(prims: &[Node], idx: usize) -> bool {
+    let node = &prims[idx];
+    let input = get_input(node);
+    let implicit_input = get_implicit_input(prims, idx);
+
+    let is_implicit = input.is_none() || input == implicit_input;
+
+    // the result of the last primitive is the filter result
+    if idx == prims.len() - 1 && !is_implicit {
+        return false;
+    }
+
+    // the input name that should be used instead of the primitive result
+    let new_input = if is_implicit { implicit_input } else { input.clone() };
+
+    let result = value_to_string(node.attribute_value(AId::Result).as_ref());
+    if let Some(ref result) = result {
+        let refs = collect_result_refs(prims, idx, result);
+        if !refs.is_empty() {
+            let new_input = match new_input {
+                Some(ref v) => v,
+                // the input is a result of the previous primitive without a name
+                None => return false,
+            };
+
+            for (n, aid) in refs {
+                n.set_attribute(aid, new_input.clone());
+            }
+        }
+    }
+
+    if !is_implicit {
+        // the next primitive expects the current primitive result as an input
+        if let Some(next) = prims.get(idx + 1) {
+            if !next.has_attribute(AId::In) {
+                // unwrap is safe, because an explicit input is set
+                next.set_attribute(AId::In, input.unwrap());
+            }
+        }
+    }
+
+    node.remove();
+
+    true
+}
+
+// Collects 'in' and 'in2' attributes that reference the result of the specified primitive.
+fn collect_result_refs(prims: &[Node], idx: usize, result: &str) -> Vec<(Node, AId)> {
+    let mut refs = Vec::new();
+
+    for prim in prims.iter().skip(idx + 1) {
+        // 'feMerge' has inputs in the 'feMergeNode' children
+        for n in prim.descendants().svg() {
+            for aid in &[AId::In, AId::In2] {
+                if value_to_string(n.attribute_value(*aid).as_ref()).as_ref().map(|s| s.as_str())
+                    == Some(result) {
+                    refs.push((n.clone(), *aid));
+                }
+            }
+        }
+
+        // the result name is redefined, so the next primitives will reference it
+        if value_to_string(prim.attribute_value(AId::Result).as_ref()).as_ref()
+               .map(|s| s.as_str()) == Some(result) {
+            break;
+        }
+    }
+
+    refs
+}
+
+// Removes 'in' attributes that are equal to the default input.
+fn rm_implicit_inputs(filter: &Node) {
+    let prims: Vec<Node> = filter.children().svg().collect();
+
+    for (i, node) in prims.iter().enumerate() {
+        if let Some(input) = get_input(node) {
+            if Some(input) == get_implicit_input(&prims, i) {
+                node.remove_attribute(AId::In);
+            }
+        }
+    }
+}
+
+// Removes 'result' attributes that are not referenced by any primitive.
+fn rm_unused_results(filter: &Node) {
+    let prims: Vec<Node> = filter.children().svg().collect();
+
+    for (i, node) in prims.iter().enumerate() {
+        if let Some(result) = value_to_string(node.attribute_value(AId::Result).as_ref()) {
+            if collect_result_refs(&prims, i, &result).is_empty() {
+                node.remove_attribute(AId::Result);
+            }
+        }
+    }
+}
+
+fn get_input(node: &Node) -> Option<String> {
+    value_to_string(node.attribute_value(AId::In).as_ref())
+}
+
+// 'If no value is provided and this is the first filter primitive, then this filter
+// primitive will use SourceGraphic as its input. If no value is provided and this is
+// a subsequent filter primitive, then this filter primitive will use the result from
+// the previous filter primitive as its input.'
+//
+// Returns `None` when the previous primitive doesn't have a result name.
+fn get_implicit_input(prims: &[Node], idx: usize) -> Option<String> {
+    if idx == 0 {
+        Some("SourceGraphic".to_string())
+    } else {
+        value_to_string(prims[idx - 1].attribute_value(AId::Result).as_ref())
+    }
+}
+
+fn value_to_string(value: Option<&AttributeValue>) -> Option<String> {
+    let value = try_opt!(value);
+    let mut buf = Vec::new();
+    value.write_buf_opt(&WriteOptions::default(), &mut buf);
+    Some(String::from_utf8_lossy(&buf).into_owned())
+}
+
+// Removes default filter region attributes and converts the 'userSpaceOnUse' region
+// into the 'objectBoundingBox' one, when it's shorter.
+fn process_filter_region(filter: &Node, opt: &WriteOptions) {
+    let aids = [AId::X, AId::Y, AId::Width, AId::Height];
+
+    if filter.has_attribute_with_value(AId::FilterUnits, ValueId::UserSpaceOnUse) {
+        conv_region_to_bbox(filter, opt);
+    }
+
+    if filter.has_attribute_with_value(AId::FilterUnits, ValueId::UserSpaceOnUse) {
+        return;
+    }
+
+    for aid in &aids {
+        let def = if *aid == AId::X || *aid == AId::Y { -0.1 } else { 1.2 };
+
+        if let Some(AttributeValue::Length(len)) = filter.attribute_value(*aid) {
+            let n = match len.unit {
+                Unit::None => len.num,
+                Unit::Percent => len.num / 100.0,
+                _ => continue,
+            };
+
+            if is_eq_num(n, def, opt) {
+                filter.remove_attribute(*aid);
+            }
+        }
+    }
+}
+
+fn conv_region_to_bbox(filter: &Node, opt: &WriteOptions) {
+    let aids = [AId::X, AId::Y, AId::Width, AId::Height];
+
+    // the filter must be used only by a single shape
+    let mut links = filter.linked_nodes();
+    let shape = match links.next() {
+        Some(n) => n,
+        None => return,
+    };
+    if links.any(|n| n != shape) {
+        return;
+    }
+
+    let bbox = match geom::calc_shape_bbox(&shape) {
+        Some(v) => v,
+        None => return,
+    };
+
+    // region attributes must be set explicitly, because the default values
+    // are relative to the viewport
+    let mut values = Vec::with_capacity(4);
+    for aid in &aids {
+        match filter.attribute_value(*aid) {
+            Some(AttributeValue::Length(len)) if len.unit == Unit::None => values.push(len.num),
+            _ => return,
+        }
+    }
+
+    let w = bbox.x2 - bbox.x1;
+    let h = bbox.y2 - bbox.y1;
+    let new_values = [
+        (values[0] - bbox.x1) / w,
+        (values[1] - bbox.y1) / h,
+        values[2] / w,
+        values[3] / h,
+    ];
+    let defs = [-0.1, -0.1, 1.2, 1.2];
+
+    let mut old_len = attr_len(AId::FilterUnits,
+                               AttributeValue::PredefValue(ValueId::UserSpaceOnUse), opt);
+    let mut new_len = 0;
+    for i in 0..4 {
+        old_len += attr_len(aids[i], AttributeValue::Length(Length::new(values[i], Unit::None)), opt);
+
+        if !is_eq_num(new_values[i], defs[i], opt) {
+            new_len += attr_len(aids[i], AttributeValue::Number(new_values[i]), opt);
+        }
+    }
+
+    if new_len < old_len {
+        filter.remove_attribute(AId::FilterUnits);
+        for i in 0..4 {
+            filter.set_attribute(aids[i], (new_values[i], Unit::None));
+        }
+    }
+}
+
+// Removes 'primitiveUnits' when no primitive depends on it.
+fn process_primitive_units(filter: &Node) {
+    if !filter.has_attribute(AId::PrimitiveUnits) {
+        return;
+    }
+
+    let aids = [
+        AId::X, AId::Y, AId::Z, AId::Width, AId::Height,
+        AId::Dx, AId::Dy, AId::StdDeviation, AId::Radius, AId::Scale, AId::KernelUnitLength,
+        AId::PointsAtX, AId::PointsAtY, AId::PointsAtZ,
+    ];
+
+    if !filter.descendants().svg().skip(1).any(|n| n.has_attributes(&aids)) {
+        filter.remove_attribute(AId::PrimitiveUnits);
+    }
+}
+
+// name + ='' + space
+fn attr_len(aid: AId, value: AttributeValue, opt: &WriteOptions) -> usize {
+    let mut buf = Vec::new();
+    value.write_buf_opt(opt, &mut buf);
+    aid.name().len() + buf.len() + 4
+}
+
+fn is_eq_num(n1: f64, n2: f64, opt: &WriteOptions) -> bool {
+    let mut buf1 = Vec::new();
+    AttributeValue::Number(n1).write_buf_opt(opt, &mut buf1);
+    let mut buf2 = Vec::new();
+    AttributeValue::Number(n2).write_buf_opt(opt, &mut buf2);
+    buf1 == buf2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::{Document, WriteToString};
+
+    macro_rules! test {
+        ($name:ident, $in_text:expr, $out_text:expr) => (
+            #[test]
+            fn $name() {
+                let doc = Document::from_data($in_text).unwrap();
+                optimize_filters(&doc, &write_opt_for_tests!());
+                assert_eq_text!(doc.to_string_with_opt(&write_opt_for_tests!()), $out_text);
+            }
+        )
+    }
+
+    macro_rules! test_eq {
+        ($name:ident, $in_text:expr) => (
+            test!($name, $in_text, String::from_utf8_lossy($in_text));
+        )
+    }
+
+    test!(rm_passthrough_1,
+b"<svg>
+    <filter id='f1'>
+        <feOffset dx='0' dy='0'/>
+        <feGaussianBlur stdDeviation='2'/>
+        <feComponentTransfer/>
+    </filter>
+</svg>",
+"<svg>
+    <filter id='f1'>
+        <feGaussianBlur stdDeviation='2'/>
+    </filter>
+</svg>
+");
+
+    test!(rm_passthrough_2,
+b"<svg>
+    <filter id='f1'>
+        <feGaussianBlur stdDeviation='0 0'/>
+        <feComponentTransfer>
+            <feFuncR type='identity'/>
+            <feFuncA type='identity'/>
+        </feComponentTransfer>
+        <feOffset dx='5'/>
+    </filter>
+</svg>",
+"<svg>
+    <filter id='f1'>
+        <feOffset dx='5'/>
+    </filter>
+</svg>
+");
+
+    test!(rm_passthrough_with_result_1,
+b"<svg>
+    <filter id='f1'>
+        <feGaussianBlur in='SourceAlpha' stdDeviation='0' result='blur'/>
+        <feOffset dx='5'/>
+        <feMerge>
+            <feMergeNode in='blur'/>
+            <feMergeNode in='SourceGraphic'/>
+        </feMerge>
+    </filter>
+</svg>",
+"<svg>
+    <filter id='f1'>
+        <feOffset dx='5' in='SourceAlpha'/>
+        <feMerge>
+            <feMergeNode in='SourceAlpha'/>
+            <feMergeNode in='SourceGraphic'/>
+        </feMerge>
+    </filter>
+</svg>
+");
+
+    test_eq!(keep_passthrough_1,
+b"<svg>
+    <filter id='f1'>
+        <feOffset dx='0' dy='0'/>
+    </filter>
+</svg>
+");
+
+    test_eq!(keep_passthrough_2,
+b"<svg>
+    <filter id='f1'>
+        <feFlood/>
+        <feOffset dx='0' in='SourceAlpha'/>
+    </filter>
+</svg>
+");
+
+    test_eq!(keep_passthrough_3,
+b"<svg>
+    <filter id='f1'>
+        <feOffset dx='0' height='10'/>
+        <feFlood/>
+    </filter>
+</svg>
+");
+
+    test!(rm_implicit_input_1,
+b"<svg>
+    <filter id='f1'>
+        <feGaussianBlur in='SourceGraphic' result='r1' stdDeviation='2'/>
+        <feOffset dx='5' in='r1' result='r2'/>
+    </filter>
+</svg>",
+"<svg>
+    <filter id='f1'>
+        <feGaussianBlur stdDeviation='2'/>
+        <feOffset dx='5'/>
+    </filter>
+</svg>
+");
+
+    test_eq!(keep_result_1,
+b"<svg>
+    <filter id='f1'>
+        <feGaussianBlur result='r1' stdDeviation='2'/>
+        <feOffset dx='5'/>
+        <feBlend in2='r1'/>
+    </filter>
+</svg>
+");
+
+    test!(rm_default_region_1,
+b"<svg>
+    <filter id='f1' height='1.2' width='120%' x='-0.1' y='-10%'>
+        <feFlood/>
+    </filter>
+</svg>",
+"<svg>
+    <filter id='f1'>
+        <feFlood/>
+    </filter>
+</svg>
+");
+
+    test!(conv_region_1,
+b"<svg>
+    <filter id='f1' filterUnits='userSpaceOnUse' height='100' width='120' x='-10' y='0'>
+        <feFlood/>
+    </filter>
+    <rect filter='url(#f1)' height='50' width='100' y='25'/>
+</svg>",
+"<svg>
+    <filter id='f1' height='2' y='-0.5'>
+        <feFlood/>
+    </filter>
+    <rect filter='url(#f1)' height='50' width='100' y='25'/>
+</svg>
+");
+
+    test!(rm_primitive_units_1,
+b"<svg>
+    <filter id='f1' primitiveUnits='objectBoundingBox'>
+        <feFlood/>
+    </filter>
+</svg>",
+"<svg>
+    <filter id='f1'>
+        <feFlood/>
+    </filter>
+</svg>
+");
+
+    test_eq!(keep_primitive_units_1,
+b"<svg>
+    <filter id='f1' primitiveUnits='objectBoundingBox'>
+        <feGaussianBlur stdDeviation='0.1'/>
+    </filter>
+</svg>
+");
+
+}
@@ -20,7 +20,7 @@
 **
 ****************************************************************************/
 
-use svgdom::{Document, AttributeValue};
+use svgdom::{Document, AttributeValue, WriteOptions};
 use svgdom::types::path::Path;
 
 use task::short::{EId, AId};
@@ -28,8 +28,9 @@ use options::Options;
 
 mod conv_segments;
 mod rm_unused;
+mod round_coords;
 
-pub fn process_paths(doc: &Document, options: &Options) {
+pub fn process_paths(doc: &Document, options: &Options, opt: &WriteOptions) {
     for node in doc.descendants().svg().filter(|n| n.is_tag_name(EId::Path)) {
         // We can't process paths with marker, because if we remove all segments
         // it will break rendering.
@@ -39,12 +40,12 @@ pub fn process_paths(doc: &Document, options: &Options) {
 
         let mut attrs = node.attributes_mut();
         if let Some(&mut AttributeValue::Path(ref mut path)) = attrs.get_value_mut(AId::D) {
-            process_path(path, has_marker, options);
+            process_path(path, has_marker, options, opt.paths.coordinates_precision);
         }
     }
 }
 
-fn process_path(path: &mut Path, has_marker: bool, options: &Options) {
+pub fn process_path(path: &mut Path, has_marker: bool, options: &Options, precision: u8) {
     path.conv_to_absolute();
 
     if options.convert_segments {
@@ -55,6 +56,10 @@ fn process_path(path: &mut Path, has_marker: bool, options: &Options) {
         rm_unused::remove_unused_segments(path);
     }
 
+    // Calculate relative coordinates from the rounded absolute ones,
+    // otherwise the rounding error will be accumulated on the whole path.
+    round_coords::round_coordinates(path, precision);
+
     path.conv_to_relative();
 
     // TODO: estimate path length
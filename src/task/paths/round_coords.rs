@@ -0,0 +1,100 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+use svgdom::types::path::{Path, Segment, SegmentData};
+
+// Rounds absolute coordinates to the specified precision.
+//
+// Relative coordinates calculated from the rounded absolute ones are exact
+// in this precision, so the rounding error will not be accumulated along the path.
+pub fn round_coordinates(path: &mut Path, precision: u8) {
+    let k = 10f64.powi(precision as i32);
+    let round = |n: f64| (n * k).round() / k;
+
+    for seg in &mut path.d {
+        let new_seg = match *seg.data() {
+            SegmentData::MoveTo { x, y } => {
+                Segment::new_move_to(round(x), round(y))
+            }
+            SegmentData::LineTo { x, y } => {
+                Segment::new_line_to(round(x), round(y))
+            }
+            SegmentData::HorizontalLineTo { x } => {
+                Segment::new_hline_to(round(x))
+            }
+            SegmentData::VerticalLineTo { y } => {
+                Segment::new_vline_to(round(y))
+            }
+            SegmentData::CurveTo { x1, y1, x2, y2, x, y } => {
+                Segment::new_curve_to(round(x1), round(y1), round(x2), round(y2),
+                                      round(x), round(y))
+            }
+            SegmentData::SmoothCurveTo { x2, y2, x, y } => {
+                Segment::new_smooth_curve_to(round(x2), round(y2), round(x), round(y))
+            }
+            SegmentData::Quadratic { x1, y1, x, y } => {
+                Segment::new_quad_to(round(x1), round(y1), round(x), round(y))
+            }
+            SegmentData::SmoothQuadratic { x, y } => {
+                Segment::new_smooth_quad_to(round(x), round(y))
+            }
+            SegmentData::EllipticalArc { rx, ry, x_axis_rotation, large_arc, sweep, x, y } => {
+                // radii and rotation are not accumulated
+                Segment::new_arc_to(rx, ry, x_axis_rotation, large_arc, sweep, round(x), round(y))
+            }
+            SegmentData::ClosePath => continue,
+        };
+
+        *seg = new_seg;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::{FromStream};
+    use svgdom::types::path::{Path};
+
+    macro_rules! test {
+        ($name:ident, $precision:expr, $in_text:expr, $out_text:expr) => (
+            #[test]
+            fn $name() {
+                let mut path = Path::from_data($in_text).unwrap();
+                path.conv_to_absolute();
+                round_coordinates(&mut path, $precision);
+                path.conv_to_relative();
+                assert_eq_text!(path.to_string(), $out_text);
+            }
+        )
+    }
+
+    // without rounding, all relative coordinates would be '0.1'
+    // and the path end would drift to '0.4'
+    test!(round_1, 1, b"M 0 0 H 0.14 H 0.28 H 0.42 H 0.56",
+                       "m 0 0 h 0.1 h 0.2 h 0.1 h 0.2");
+
+    test!(round_2, 0, b"M 0.4 0.4 h 10.4 v 10.4 h -10.4 z",
+                       "m 0 0 h 11 v 11 h -11 z");
+
+    test!(round_3, 1, b"M 10.04 10.04 C 10.16 10.16 20.26 20.26 30.37 30.37",
+                       "m 10 10 c 0.2 0.2 10.3 10.3 20.4 20.4");
+}
@@ -0,0 +1,272 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+use super::short::{EId, AId, Unit};
+use super::apply_transforms::utils as ts_utils;
+use super::fit_viewbox::{transform_content, is_graphic_element};
+use super::ungroup_svg::has_percent_values;
+
+use geom;
+
+use svgdom::{Document, Node, AttributeValue};
+use svgdom::types::Transform;
+
+// Moves the content center to the origin and updates the 'viewBox' origin accordingly,
+// so the rendering result is not changed.
+//
+// If `precision` is set, the coordinate system can also be scaled by a power of ten,
+// when it makes paths coordinates shorter. The scale is applied only when it can be
+// baked into the paths data.
+pub fn rebase_coordinates(doc: &Document, precision: Option<u8>) {
+    // doc must contain 'svg' node, so we can safely unwrap
+    let svg = doc.svg_element().unwrap();
+
+    // nested 'svg' elements can't be transformed
+    if svg.children().svg().any(|n| n.is_tag_name(EId::Svg)) {
+        return;
+    }
+
+    // percentage values are resolved relative to the 'viewBox'
+    if svg.descendants().svg().any(|n| n != svg && has_percent_values(&n)) {
+        return;
+    }
+
+    let view_box = match svg.attribute_value(AId::ViewBox) {
+        Some(AttributeValue::NumberList(list)) => list,
+        Some(_) => return,
+        None => {
+            // without the 'viewBox' the user unit is equal to the viewport unit
+            match (svg.attribute_value(AId::Width), svg.attribute_value(AId::Height)) {
+                (Some(AttributeValue::Length(w)), Some(AttributeValue::Length(h)))
+                    if w.unit == Unit::None && h.unit == Unit::None => {
+                    vec![0.0, 0.0, w.num, h.num]
+                }
+                _ => return,
+            }
+        }
+    };
+
+    if view_box.len() != 4 {
+        return;
+    }
+
+    let bbox = match geom::calc_bbox(&svg, false) {
+        Some(Some(r)) => r,
+        _ => return,
+    };
+
+    let cx = (bbox.x1 + bbox.x2) / 2.0;
+    let cy = (bbox.y1 + bbox.y2) / 2.0;
+
+    let nodes: Vec<Node> = svg.children().svg().filter(is_graphic_element).collect();
+
+    let scale = match precision {
+        Some(p) if can_bake(&nodes, &Transform::new(10.0, 0.0, 0.0, 10.0, 0.0, 0.0)) => {
+            10f64.powi(calc_scale_power(&nodes, cx, cy, p))
+        }
+        _ => 1.0,
+    };
+
+    // use a round offset to keep the 'viewBox' values short
+    let dx = (scale * cx).round();
+    let dy = (scale * cy).round();
+
+    if dx == 0.0 && dy == 0.0 && scale == 1.0 {
+        return;
+    }
+
+    let ts = Transform::new(scale, 0.0, 0.0, scale, -dx, -dy);
+    if can_bake(&nodes, &ts) {
+        for node in &nodes {
+            ts_utils::bake_transform(node, &ts);
+        }
+    } else {
+        transform_content(doc, &svg, ts);
+    }
+
+    let new_view_box = vec![
+        view_box[0] * scale - dx,
+        view_box[1] * scale - dy,
+        view_box[2] * scale,
+        view_box[3] * scale,
+    ];
+    svg.set_attribute(AId::ViewBox, AttributeValue::NumberList(new_view_box));
+}
+
+fn can_bake(nodes: &[Node], ts: &Transform) -> bool {
+    nodes.iter().all(|n| !n.is_used() && ts_utils::can_bake_transform(n, ts))
+}
+
+// Finds a power of ten, which gives the shortest paths coordinates.
+//
+// The paths length is estimated using the segments end points.
+fn calc_scale_power(nodes: &[Node], cx: f64, cy: f64, precision: u8) -> i32 {
+    let mut xs = Vec::new();
+    let mut ys = Vec::new();
+    for node in nodes {
+        if let Some(AttributeValue::Path(mut path)) = node.attribute_value(AId::D) {
+            path.conv_to_absolute();
+            for seg in &path.d {
+                if let Some(x) = seg.x() {
+                    xs.push(x);
+                }
+
+                if let Some(y) = seg.y() {
+                    ys.push(y);
+                }
+            }
+        }
+    }
+
+    let mut best_power = 0;
+    let mut best_len = None;
+    for power in 0..(precision + 1) {
+        let k = 10f64.powi(power as i32);
+        // the same offset as in 'rebase_coordinates'
+        let dx = (k * cx).round();
+        let dy = (k * cy).round();
+        let p = precision - power;
+
+        let len: usize =   xs.iter().map(|x| number_len(x * k - dx, p)).sum::<usize>()
+                         + ys.iter().map(|y| number_len(y * k - dy, p)).sum::<usize>();
+
+        if best_len.map_or(true, |l| len < l) {
+            best_power = power as i32;
+            best_len = Some(len);
+        }
+    }
+
+    best_power
+}
+
+// Returns a length of the number written with the specified precision.
+fn number_len(n: f64, precision: u8) -> usize {
+    let mut s = format!("{:.*}", precision as usize, n);
+    if s.contains('.') {
+        while s.ends_with('0') {
+            s.pop();
+        }
+
+        if s.ends_with('.') {
+            s.pop();
+        }
+    }
+
+    if s == "-0" {
+        return 1;
+    }
+
+    // the leading zero is removed
+    if s.starts_with("0.") || s.starts_with("-0.") {
+        s.len() - 1
+    } else {
+        s.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::{Document, WriteToString};
+
+    macro_rules! test {
+        ($name:ident, $precision:expr, $in_text:expr, $out_text:expr) => (
+            base_test!($name, |doc: &Document| rebase_coordinates(doc, $precision), $in_text, $out_text);
+        )
+    }
+
+    macro_rules! test_eq {
+        ($name:ident, $in_text:expr) => (
+            test!($name, None, $in_text, String::from_utf8_lossy($in_text));
+        )
+    }
+
+    test!(rebase_1, None,
+b"<svg viewBox='12300 4500 200 100'>
+    <rect height='10' width='20' x='12390' y='4540'/>
+</svg>",
+"<svg viewBox='-100 -45 200 100'>
+    <rect height='10' transform='translate(-12400 -4545)' width='20' x='12390' y='4540'/>
+</svg>
+");
+
+    test!(rebase_2, None,
+b"<svg height='100' width='200'>
+    <rect height='10' width='20' x='90' y='40'/>
+</svg>",
+"<svg height='100' viewBox='-100 -45 200 100' width='200'>
+    <rect height='10' transform='translate(-100 -45)' width='20' x='90' y='40'/>
+</svg>
+");
+
+    test!(rebase_scale_1, Some(1),
+b"<svg viewBox='0 0 11 11'>
+    <path d='M 0.5 0.5 L 10.5 0.5 L 10.5 10.5 L 0.5 10.5 L 0.5 5.5 L 5.5 5.5 Z' stroke='#000000' stroke-width='0.5'/>
+</svg>",
+"<svg viewBox='-55 -55 110 110'>
+    <path d='M -50 -50 L 50 -50 L 50 50 L -50 50 L -50 0 L 0 0 Z' stroke='#000000' stroke-width='5'/>
+</svg>
+");
+
+    // coordinates are already integers, so only the translation is applied
+    test!(rebase_scale_2, Some(8),
+b"<svg viewBox='0 0 20 20'>
+    <path d='M 0 0 L 20 20' stroke='#000000'/>
+</svg>",
+"<svg viewBox='-10 -10 20 20'>
+    <path d='M -10 -10 L 10 10' stroke='#000000'/>
+</svg>
+");
+
+    #[test]
+    fn rebase_scale_len() {
+        let text = b"<svg viewBox='0 0 11 11'>
+    <path d='M 0.5 0.5 L 10.5 0.5 L 10.5 10.5 L 0.5 10.5 L 0.5 5.5 L 5.5 5.5 Z' stroke='#000000' stroke-width='0.5'/>
+</svg>";
+        let doc = Document::from_data(text).unwrap();
+        let old_len = doc.to_string_with_opt(&write_opt_for_tests!()).len();
+        rebase_coordinates(&doc, Some(1));
+        let new_len = doc.to_string_with_opt(&write_opt_for_tests!()).len();
+        assert!(new_len < old_len);
+    }
+
+    #[test]
+    fn number_len_1() {
+        assert_eq!(number_len(0.5, 1), 2);
+        assert_eq!(number_len(-0.04, 1), 1);
+        assert_eq!(number_len(-12.50, 2), 5);
+        assert_eq!(number_len(100.0, 0), 3);
+    }
+
+    test_eq!(keep_1,
+b"<svg viewBox='-100 -100 200 200'>
+    <rect height='20' width='20' x='-10' y='-10'/>
+</svg>
+");
+
+    // the viewport size is unknown
+    test_eq!(keep_2,
+b"<svg>
+    <rect height='10' width='20' x='90' y='40'/>
+</svg>
+");
+}
@@ -20,6 +20,10 @@
 **
 ****************************************************************************/
 
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+
 use task::short::{EId, AId};
 
 use svgdom::{Document, Node};
@@ -27,12 +31,45 @@ use svgdom::{Document, Node};
 use svgdom_utils;
 
 pub fn regroup_gradient_stops(doc: &Document) {
-    let mut nodes: Vec<Node> = doc.descendants().svg()
+    let nodes: Vec<Node> = doc.descendants().svg()
         .filter(|n| super::is_gradient(n))
         .filter(|n| n.has_children())
         .filter(|n| !n.has_attribute(AId::XlinkHref))
         .collect();
 
+    // Split gradients into buckets by stops hash, so we compare only gradients
+    // that can have equal stops.
+    let mut buckets: Vec<Vec<Node>> = Vec::new();
+    {
+        let mut map: HashMap<u64, usize> = HashMap::new();
+        for node in nodes {
+            let mut hasher = DefaultHasher::new();
+            super::rm_dupl_defs::hash_stops(&mut hasher, &node);
+
+            let idx = *map.entry(hasher.finish()).or_insert_with(|| {
+                buckets.push(Vec::new());
+                buckets.len() - 1
+            });
+            buckets[idx].push(node);
+        }
+    }
+
+    let mut id_gen = IdGenerator::new(doc, "lg");
+
+    let mut is_changed = false;
+    for mut bucket in buckets {
+        if bucket.len() > 1 && regroup_bucket(doc, &mut bucket, &mut id_gen) {
+            is_changed = true;
+        }
+    }
+
+    if is_changed {
+        // we must resolve attributes for gradients created above
+        svgdom_utils::resolve_linear_gradient_attributes(doc);
+    }
+}
+
+fn regroup_bucket(doc: &Document, nodes: &mut Vec<Node>, id_gen: &mut IdGenerator) -> bool {
     let mut is_changed = false;
     let mut join_nodes = Vec::new();
     let mut i1 = 0;
@@ -56,8 +93,7 @@ pub fn regroup_gradient_stops(doc: &Document) {
             is_changed = true;
 
             let new_lg = doc.create_element(EId::LinearGradient);
-            let new_id = gen_id(doc, "lg");
-            new_lg.set_id(new_id);
+            new_lg.set_id(id_gen.next());
 
             while node1.has_children() {
                 let c = node1.children().nth(0).unwrap();
@@ -84,29 +120,35 @@ pub fn regroup_gradient_stops(doc: &Document) {
         i1 += 1;
     }
 
-    if is_changed {
-        // we must resolve attributes for gradients created above
-        svgdom_utils::resolve_linear_gradient_attributes(doc);
-    }
+    is_changed
 }
 
-fn gen_id(doc: &Document, prefix: &str) -> String {
-    let mut n = 1;
-
-    let mut s = String::new();
-    loop {
-        s.clear();
-        s.push_str(prefix);
-        s.push_str(&n.to_string());
+// Generates unique ids without checking the whole document each time.
+struct IdGenerator {
+    ids: HashSet<String>,
+    prefix: &'static str,
+    n: usize,
+}
 
-        if !doc.descendants().svg().any(|n| *n.id() == s) {
-            break;
+impl IdGenerator {
+    fn new(doc: &Document, prefix: &'static str) -> IdGenerator {
+        IdGenerator {
+            ids: doc.descendants().svg().map(|n| n.id().clone()).collect(),
+            prefix: prefix,
+            n: 1,
         }
-
-        n += 1;
     }
 
-    s
+    fn next(&mut self) -> String {
+        loop {
+            let s = format!("{}{}", self.prefix, self.n);
+            self.n += 1;
+
+            if !self.ids.contains(&s) {
+                return s;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,364 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+use std::collections::HashMap;
+
+use super::short::{EId, AId};
+
+use svgdom::{Document, Node, AttributeValue, WriteOptions, WriteBuffer};
+use svgdom::types::{FuzzyEq, Transform};
+
+// Replaces repeated elements with links to a single element inside the 'defs'.
+//
+// Elements are compared by their serialized content, excluding the 'id' attribute
+// and a translate-only 'transform' of the top-level element.
+pub fn replace_dupl_with_use(doc: &Document, opt: &WriteOptions) {
+    // doc must contain 'svg' node, so we can safely unwrap
+    let svg = doc.svg_element().unwrap();
+
+    let mut items = Vec::new();
+    collect_items(&svg, 0, opt, &mut items);
+
+    // group equal elements
+    let mut map: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (idx, item) in items.iter().enumerate() {
+        if let Some(ref key) = item.key {
+            map.entry(key).or_insert_with(Vec::new).push(idx);
+        }
+    }
+
+    // Process the biggest elements first, so the children of the replaced elements
+    // will not be processed.
+    let mut groups: Vec<Vec<usize>> = map.into_iter()
+        .map(|(_, list)| list)
+        .filter(|list| list.len() > 1)
+        .collect();
+    groups.sort_by(|a, b| {
+        key_len(&items, b).cmp(&key_len(&items, a)).then(a[0].cmp(&b[0]))
+    });
+
+    let mut skip = vec![false; items.len()];
+    for list in groups {
+        let list: Vec<usize> = list.into_iter().filter(|i| !skip[*i]).collect();
+        if list.len() < 2 {
+            continue;
+        }
+
+        if !is_use_shorter(&items, &list, opt) {
+            continue;
+        }
+
+        for &i in &list {
+            skip[i] = true;
+
+            // skip children
+            let mut j = i + 1;
+            while j < items.len() && items[j].depth > items[i].depth {
+                skip[j] = true;
+                j += 1;
+            }
+        }
+
+        replace(doc, &items, &list);
+    }
+}
+
+fn key_len(items: &[Item], list: &[usize]) -> usize {
+    // unwrap is safe, because only items with a key are grouped
+    items[list[0]].key.as_ref().unwrap().len()
+}
+
+struct Item {
+    node: Node,
+    depth: usize,
+    // serialized element content, if the element can be replaced
+    key: Option<String>,
+    // translate part of the element transform
+    offset: (f64, f64),
+    // length of the element transform
+    ts_len: usize,
+}
+
+// Collects elements in the document order.
+fn collect_items(parent: &Node, depth: usize, opt: &WriteOptions, items: &mut Vec<Item>) {
+    for node in parent.children().svg() {
+        if !is_valid_elem(&node) {
+            continue;
+        }
+
+        let ts = {
+            let attrs = node.attributes();
+            get_value!(attrs, Transform, AId::Transform, Transform::default())
+        };
+        let is_translate =    ts.a.fuzzy_eq(&1.0) && ts.b.fuzzy_eq(&0.0)
+                           && ts.c.fuzzy_eq(&0.0) && ts.d.fuzzy_eq(&1.0);
+
+        let key = if is_translate && !node.is_used() {
+            gen_key(&node, true, opt)
+        } else {
+            None
+        };
+
+        let ts_len = if node.has_attribute(AId::Transform) {
+            let mut buf = Vec::new();
+            AttributeValue::Transform(ts).write_buf_opt(opt, &mut buf);
+            // transform=""
+            buf.len() + 13
+        } else {
+            0
+        };
+
+        items.push(Item {
+            node: node.clone(),
+            depth: depth,
+            key: key,
+            offset: (ts.e, ts.f),
+            ts_len: ts_len,
+        });
+
+        if node.is_tag_name(EId::G) {
+            collect_items(&node, depth + 1, opt, items);
+        }
+    }
+}
+
+fn is_valid_elem(node: &Node) -> bool {
+    match node.tag_id().unwrap() {
+          EId::G
+        | EId::Path
+        | EId::Rect
+        | EId::Circle
+        | EId::Ellipse
+        | EId::Line
+        | EId::Polyline
+        | EId::Polygon
+        | EId::Use => true,
+        _ => false,
+    }
+}
+
+// Serializes an element with all its children.
+//
+// Returns `None` if the element can't be replaced.
+fn gen_key(node: &Node, is_root: bool, opt: &WriteOptions) -> Option<String> {
+    // referenced elements can't be moved
+    if !is_root && node.is_used() {
+        return None;
+    }
+
+    if !is_valid_elem(node) {
+        return None;
+    }
+
+    let mut buf = Vec::new();
+    buf.push(b'<');
+    buf.extend_from_slice(node.tag_id().unwrap().name().as_bytes());
+
+    for (aid, attr) in node.attributes().iter_svg() {
+        if aid == AId::Id || (is_root && aid == AId::Transform) {
+            continue;
+        }
+
+        buf.push(b' ');
+        buf.extend_from_slice(aid.name().as_bytes());
+        buf.extend_from_slice(b"='");
+        attr.value.write_buf_opt(opt, &mut buf);
+        buf.push(b'\'');
+    }
+
+    if node.has_children() {
+        buf.push(b'>');
+        for child in node.children().svg() {
+            let key = try_opt!(gen_key(&child, false, opt));
+            buf.extend_from_slice(key.as_bytes());
+        }
+        buf.extend_from_slice(b"</");
+        buf.extend_from_slice(node.tag_id().unwrap().name().as_bytes());
+        buf.push(b'>');
+    } else {
+        buf.extend_from_slice(b"/>");
+    }
+
+    // unwrap can't fail
+    Some(String::from_utf8(buf).unwrap())
+}
+
+// Checks that 'use' elements will produce a smaller output than the original elements.
+fn is_use_shorter(items: &[Item], list: &[usize], opt: &WriteOptions) -> bool {
+    let elem_len = key_len(items, list);
+
+    // id='el1'
+    let id_len = 10;
+
+    let mut old_len = 0;
+    let mut new_len = elem_len + id_len;
+    for &i in list {
+        let item = &items[i];
+
+        old_len += elem_len + item.ts_len;
+
+        // <use xlink:href='#el1'/>
+        new_len += 24;
+
+        let (x, y) = item.offset;
+        if x.fuzzy_ne(&0.0) {
+            // x=''
+            new_len += 5 + number_len(x, opt);
+        }
+
+        if y.fuzzy_ne(&0.0) {
+            new_len += 5 + number_len(y, opt);
+        }
+    }
+
+    new_len < old_len
+}
+
+fn number_len(n: f64, opt: &WriteOptions) -> usize {
+    let mut buf = Vec::new();
+    AttributeValue::Number(n).write_buf_opt(opt, &mut buf);
+    buf.len()
+}
+
+fn replace(doc: &Document, items: &[Item], list: &[usize]) {
+    // the first element is moved to the 'defs'
+    let elem = items[list[0]].node.clone();
+
+    if !elem.has_attribute(AId::Id) {
+        elem.set_id(::task::utils::gen_id(doc, "el"));
+    }
+
+    for &i in list {
+        let node = &items[i].node;
+
+        let use_node = doc.create_element(EId::Use);
+        let (x, y) = items[i].offset;
+        if x.fuzzy_ne(&0.0) {
+            use_node.set_attribute(AId::X, x);
+        }
+        if y.fuzzy_ne(&0.0) {
+            use_node.set_attribute(AId::Y, y);
+        }
+
+        node.insert_before(&use_node);
+
+        if *node != elem {
+            node.remove();
+        }
+
+        // if it's fail - it's already a huge problem, so unwrap is harmless
+        use_node.set_link_attribute(AId::XlinkHref, elem.clone()).unwrap();
+    }
+
+    elem.detach();
+    elem.remove_attribute(AId::Transform);
+    ::task::utils::get_defs(doc).append(&elem);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::{Document, WriteToString};
+
+    macro_rules! test {
+        ($name:ident, $in_text:expr, $out_text:expr) => (
+            #[test]
+            fn $name() {
+                let doc = Document::from_data($in_text).unwrap();
+                replace_dupl_with_use(&doc, &write_opt_for_tests!());
+                assert_eq_text!(doc.to_string_with_opt(&write_opt_for_tests!()), $out_text);
+            }
+        )
+    }
+
+    macro_rules! test_eq {
+        ($name:ident, $in_text:expr) => (
+            test!($name, $in_text, String::from_utf8_lossy($in_text));
+        )
+    }
+
+    test!(replace_1,
+b"<svg>
+    <path d='M 10 20 L 30 40 L 50 60 L 70 80 L 90 100 Z'/>
+    <path d='M 10 20 L 30 40 L 50 60 L 70 80 L 90 100 Z' transform='translate(100)'/>
+    <path d='M 10 20 L 30 40 L 50 60 L 70 80 L 90 100 Z' transform='translate(200 50)'/>
+</svg>",
+"<svg>
+    <defs>
+        <path id='el1' d='M 10 20 L 30 40 L 50 60 L 70 80 L 90 100 Z'/>
+    </defs>
+    <use xlink:href='#el1'/>
+    <use x='100' xlink:href='#el1'/>
+    <use x='200' xlink:href='#el1' y='50'/>
+</svg>
+");
+
+    // the biggest subtree should be replaced
+    test!(replace_2,
+b"<svg>
+    <g fill='#ff0000'>
+        <rect height='10' width='10'/>
+        <rect height='10' width='10' x='20'/>
+    </g>
+    <g fill='#ff0000' transform='translate(50)'>
+        <rect height='10' width='10'/>
+        <rect height='10' width='10' x='20'/>
+    </g>
+</svg>",
+"<svg>
+    <defs>
+        <g id='el1' fill='#ff0000'>
+            <rect height='10' width='10'/>
+            <rect height='10' width='10' x='20'/>
+        </g>
+    </defs>
+    <use xlink:href='#el1'/>
+    <use x='50' xlink:href='#el1'/>
+</svg>
+");
+
+    // 'use' elements are longer
+    test_eq!(keep_1,
+b"<svg>
+    <rect/>
+    <rect/>
+</svg>
+");
+
+    // only translate can be moved to the 'use' element
+    test_eq!(keep_2,
+b"<svg>
+    <path d='M 10 20 L 30 40 L 50 60 L 70 80 L 90 100 Z' transform='scale(2)'/>
+    <path d='M 10 20 L 30 40 L 50 60 L 70 80 L 90 100 Z' transform='scale(3)'/>
+</svg>
+");
+
+    // referenced elements can't be moved
+    test_eq!(keep_3,
+b"<svg>
+    <path id='p1' d='M 10 20 L 30 40 L 50 60 L 70 80 L 90 100 Z'/>
+    <path id='p2' d='M 10 20 L 30 40 L 50 60 L 70 80 L 90 100 Z'/>
+    <use xlink:href='#p1'/>
+    <use xlink:href='#p2'/>
+</svg>
+");
+}
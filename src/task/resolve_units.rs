@@ -0,0 +1,497 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+use super::short::{EId, AId, Unit};
+
+use svgdom::{Document, Node, AttributeValue, ValueId};
+use svgdom::types::Length;
+
+struct Context {
+    font_size: Option<f64>,
+    // width and height of the nearest viewport
+    viewport: Option<(f64, f64)>,
+}
+
+// Converts lengths with absolute units, font-relative units and percentages
+// into user units.
+//
+// The root 'svg' element size is preserved, because it defines the size
+// in the outer coordinates.
+pub fn resolve_units(doc: &Document) {
+    // doc must contain 'svg' node, so we can safely unwrap
+    let svg = doc.svg_element().unwrap();
+
+    // Referenced elements are resolved against the viewport of the referencing element,
+    // which is not known when the document has nested viewports.
+    let has_nested_viewports = doc.descendants().svg().any(|n| {
+        match n.tag_id() {
+            Some(EId::Svg) => n != svg,
+            Some(EId::Symbol) | Some(EId::Marker) | Some(EId::Pattern) => true,
+            _ => false,
+        }
+    });
+
+    // parents are processed before children, so the inherited 'font-size'
+    // will be already resolved
+    for node in doc.descendants().svg() {
+        match get_units(&node) {
+            Some(ValueId::ObjectBoundingBox) => {
+                resolve_bbox_percents(&node);
+                continue;
+            }
+            Some(_) => {}
+            None => continue,
+        }
+
+        if is_bbox_context(&node) {
+            continue;
+        }
+
+        // Referenced elements inherit 'font-size' from the referencing element
+        // and not from their parents.
+        let is_direct = is_rendered_directly(&node);
+
+        let viewport = if has_nested_viewports && !is_direct {
+            None
+        } else {
+            find_viewport(&node)
+        };
+
+        // 'font-size' in 'em' units is relative to the parent's 'font-size'
+        if let Some(AttributeValue::Length(len)) = node.attribute_value(AId::FontSize) {
+            let parent_font_size = if is_direct {
+                node.parent().and_then(|p| resolve_font_size(&p))
+            } else {
+                None
+            };
+
+            let ctx = Context {
+                font_size: parent_font_size,
+                viewport: None,
+            };
+            if let Some(new_len) = convert_length(AId::FontSize, len, &ctx) {
+                node.set_attribute(AId::FontSize, new_len);
+            }
+        }
+
+        let font_size = if is_direct || node.has_attribute(AId::FontSize) {
+            resolve_font_size(&node)
+        } else {
+            None
+        };
+        let ctx = Context {
+            font_size: font_size,
+            viewport: viewport,
+        };
+
+        // Percentages in inherited attributes are resolved by each element separately,
+        // so they must not be inherited by a nested viewport.
+        let has_inner_viewport = node.descendants().svg().any(|n| n.is_tag_name(EId::Svg));
+        let inherited_ctx = Context {
+            font_size: font_size,
+            viewport: if has_inner_viewport { None } else { viewport },
+        };
+
+        let mut changed = Vec::new();
+        for (aid, attr) in node.attributes().iter_svg() {
+            if aid == AId::FontSize {
+                continue;
+            }
+
+            if node == svg {
+                match aid {
+                    AId::X | AId::Y | AId::Width | AId::Height => continue,
+                    _ => {}
+                }
+            }
+
+            let ctx = if attr.is_inheritable() { &inherited_ctx } else { &ctx };
+
+            match attr.value {
+                AttributeValue::Length(len) => {
+                    if let Some(new_len) = convert_length(aid, len, ctx) {
+                        changed.push((aid, AttributeValue::Length(new_len)));
+                    }
+                }
+                AttributeValue::LengthList(ref list) => {
+                    if list.iter().all(|l| l.unit == Unit::None) {
+                        continue;
+                    }
+
+                    let mut new_list = Vec::with_capacity(list.len());
+                    for len in list {
+                        if len.unit != Unit::None {
+                            match convert_length(aid, *len, ctx) {
+                                Some(new_len) => new_list.push(new_len),
+                                None => break,
+                            }
+                        } else {
+                            new_list.push(*len);
+                        }
+                    }
+
+                    // all values must be converted
+                    if new_list.len() == list.len() {
+                        changed.push((aid, AttributeValue::LengthList(new_list)));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (aid, value) in changed {
+            node.set_attribute(aid, value);
+        }
+    }
+}
+
+// Returns `None` if the length is already in user units or can't be converted.
+fn convert_length(aid: AId, len: Length, ctx: &Context) -> Option<Length> {
+    // CSS ratios, which are based on 96dpi
+    let k = match len.unit {
+        Unit::None => return None,
+        Unit::Px => 1.0,
+        Unit::In => 96.0,
+        Unit::Cm => 96.0 / 2.54,
+        Unit::Mm => 96.0 / 25.4,
+        Unit::Pt => 4.0 / 3.0,
+        Unit::Pc => 16.0,
+        Unit::Em => try_opt!(ctx.font_size),
+        // the x-height is not known, so we are using the recommended fallback value
+        Unit::Ex => try_opt!(ctx.font_size) / 2.0,
+        Unit::Percent => try_opt!(percent_base(aid, ctx)) / 100.0,
+    };
+
+    Some(Length::new(len.num * k, Unit::None))
+}
+
+fn percent_base(aid: AId, ctx: &Context) -> Option<f64> {
+    if aid == AId::FontSize {
+        return ctx.font_size;
+    }
+
+    let (w, h) = try_opt!(ctx.viewport);
+
+    match aid {
+          AId::X | AId::X1 | AId::X2 | AId::Cx | AId::Fx
+        | AId::Dx | AId::Rx | AId::Width => Some(w),
+          AId::Y | AId::Y1 | AId::Y2 | AId::Cy | AId::Fy
+        | AId::Dy | AId::Ry | AId::Height => Some(h),
+          AId::R | AId::StrokeWidth | AId::StrokeDashoffset | AId::StrokeDasharray => {
+            // non-directional lengths are relative to the normalized diagonal
+            Some(((w * w + h * h) / 2.0).sqrt())
+        }
+        _ => None,
+    }
+}
+
+// Percentages in the 'objectBoundingBox' units are fractions of the bounding box.
+fn resolve_bbox_percents(node: &Node) {
+    let mut changed = Vec::new();
+    for (aid, attr) in node.attributes().iter_svg() {
+        match aid {
+              AId::X | AId::Y | AId::Width | AId::Height
+            | AId::X1 | AId::Y1 | AId::X2 | AId::Y2
+            | AId::Cx | AId::Cy | AId::Fx | AId::Fy | AId::R => {}
+            _ => continue,
+        }
+
+        if let AttributeValue::Length(len) = attr.value {
+            if len.unit == Unit::Percent {
+                changed.push((aid, Length::new(len.num / 100.0, Unit::None)));
+            }
+        }
+    }
+
+    for (aid, len) in changed {
+        node.set_attribute(aid, len);
+    }
+}
+
+// Returns units of the element's region or coordinates attributes.
+//
+// Returns `None` when units can't be resolved.
+fn get_units(node: &Node) -> Option<ValueId> {
+    let (aid, default) = match node.tag_id().unwrap() {
+        EId::LinearGradient | EId::RadialGradient => (AId::GradientUnits, ValueId::ObjectBoundingBox),
+        EId::Pattern => (AId::PatternUnits, ValueId::ObjectBoundingBox),
+        EId::Filter => (AId::FilterUnits, ValueId::ObjectBoundingBox),
+        EId::Mask => (AId::MaskUnits, ValueId::ObjectBoundingBox),
+        _ => {
+            // filter primitives regions
+            return match node.parent() {
+                Some(ref p) if p.is_tag_name(EId::Filter) => {
+                    match p.attribute_value(AId::PrimitiveUnits) {
+                        Some(AttributeValue::PredefValue(v)) => Some(v),
+                        _ => Some(ValueId::UserSpaceOnUse),
+                    }
+                }
+                _ => Some(ValueId::UserSpaceOnUse),
+            };
+        }
+    };
+
+    match node.attribute_value(aid) {
+        Some(AttributeValue::PredefValue(v)) => Some(v),
+        // units can be inherited from the referenced element
+        _ if node.has_attribute(AId::XlinkHref) => None,
+        _ => Some(default),
+    }
+}
+
+// Returns the size of the nearest viewport.
+fn find_viewport(node: &Node) -> Option<(f64, f64)> {
+    for p in node.parents() {
+        match p.tag_id() {
+            Some(EId::Svg) => return calc_viewport_size(&p),
+            // elements inside this containers are rendered in a new viewport
+            Some(EId::Symbol) | Some(EId::Marker) | Some(EId::Pattern) => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn calc_viewport_size(svg: &Node) -> Option<(f64, f64)> {
+    let attrs = svg.attributes();
+
+    if let Some(&AttributeValue::NumberList(ref list)) = attrs.get_value(AId::ViewBox) {
+        if list.len() == 4 && list[2] > 0.0 && list[3] > 0.0 {
+            return Some((list[2], list[3]));
+        }
+
+        return None;
+    }
+
+    let ctx = Context {
+        font_size: None,
+        viewport: None,
+    };
+
+    let mut size = [0.0; 2];
+    for (i, aid) in [AId::Width, AId::Height].iter().enumerate() {
+        size[i] = match attrs.get_value(*aid) {
+            Some(&AttributeValue::Length(len)) => {
+                if len.unit == Unit::None {
+                    len.num
+                } else {
+                    try_opt!(convert_length(*aid, len, &ctx)).num
+                }
+            }
+            _ => return None,
+        };
+    }
+
+    Some((size[0], size[1]))
+}
+
+// Checks that the element is not referenced and is rendered only in the current viewport.
+fn is_rendered_directly(node: &Node) -> bool {
+    if node.is_used() {
+        return false;
+    }
+
+    node.parents().all(|p| {
+        match p.tag_id() {
+            Some(EId::Svg) | Some(EId::G) | Some(EId::A) | Some(EId::Switch) => !p.is_used(),
+            None => true,
+            _ => false,
+        }
+    })
+}
+
+// Returns the 'font-size' only when it's set explicitly and is already in user units.
+fn resolve_font_size(node: &Node) -> Option<f64> {
+    let value = if node.has_attribute(AId::FontSize) {
+        node.attribute_value(AId::FontSize)
+    } else {
+        match node.parents().find(|n| n.has_attribute(AId::FontSize)) {
+            Some(n) => n.attribute_value(AId::FontSize),
+            None => None,
+        }
+    };
+
+    match value {
+        Some(AttributeValue::Length(len)) if len.unit == Unit::None => Some(len.num),
+        _ => None,
+    }
+}
+
+// Checks that the element is inside the 'objectBoundingBox' units content,
+// where lengths have a different meaning.
+fn is_bbox_context(node: &Node) -> bool {
+    node.parents().any(|p| {
+           p.has_attribute_with_value(AId::ClipPathUnits, ValueId::ObjectBoundingBox)
+        || p.has_attribute_with_value(AId::MaskContentUnits, ValueId::ObjectBoundingBox)
+        || p.has_attribute_with_value(AId::PatternContentUnits, ValueId::ObjectBoundingBox)
+        || p.has_attribute_with_value(AId::PrimitiveUnits, ValueId::ObjectBoundingBox)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::{Document, WriteToString};
+
+    macro_rules! test {
+        ($name:ident, $in_text:expr, $out_text:expr) => (
+            base_test!($name, resolve_units, $in_text, $out_text);
+        )
+    }
+
+    macro_rules! test_eq {
+        ($name:ident, $in_text:expr) => (
+            test!($name, $in_text, String::from_utf8_lossy($in_text));
+        )
+    }
+
+    test!(conv_1,
+b"<svg height='10mm' width='10mm'>
+    <rect height='1in' width='72pt' x='2.54cm' y='6pc'/>
+</svg>",
+"<svg height='10mm' width='10mm'>
+    <rect height='96' width='96' x='96' y='96'/>
+</svg>
+");
+
+    test!(conv_2,
+b"<svg>
+    <g font-size='10'>
+        <rect height='1ex' width='2em'/>
+        <text font-size='2em' x='1em'>Text</text>
+    </g>
+</svg>",
+"<svg>
+    <g font-size='10'>
+        <rect height='5' width='20'/>
+        <text font-size='20' x='20'>Text</text>
+    </g>
+</svg>
+");
+
+    test!(conv_3,
+b"<svg>
+    <svg height='0.5in' width='0.5in'/>
+</svg>",
+"<svg>
+    <svg height='48' width='48'/>
+</svg>
+");
+
+    // 'font-size' is unknown
+    test_eq!(keep_1,
+b"<svg>
+    <rect height='1em' width='2em'/>
+</svg>
+");
+
+    test_eq!(keep_2,
+b"<svg>
+    <linearGradient id='lg1' x2='1in'/>
+    <rect height='10%' width='10'/>
+</svg>
+");
+
+    test!(conv_percent_1,
+b"<svg viewBox='0 0 200 100'>
+    <rect height='50%' width='50%' x='10%' y='10%'/>
+</svg>",
+"<svg viewBox='0 0 200 100'>
+    <rect height='50' width='100' x='20' y='10'/>
+</svg>
+");
+
+    test!(conv_percent_2,
+b"<svg height='100' width='100'>
+    <circle cx='50%' cy='50%' r='10%' stroke-width='1%'/>
+    <g font-size='10'>
+        <text font-size='200%' x='10% 20%'>Text</text>
+    </g>
+</svg>",
+"<svg height='100' width='100'>
+    <circle cx='50' cy='50' r='10' stroke-width='1'/>
+    <g font-size='10'>
+        <text font-size='20' x='10 20'>Text</text>
+    </g>
+</svg>
+");
+
+    test!(conv_percent_3,
+b"<svg>
+    <linearGradient id='lg1' x1='10%' x2='90%'/>
+    <filter id='filt1' height='140%' width='140%' x='-20%' y='-20%'/>
+</svg>",
+"<svg>
+    <linearGradient id='lg1' x1='0.1' x2='0.9'/>
+    <filter id='filt1' height='1.4' width='1.4' x='-0.2' y='-0.2'/>
+</svg>
+");
+
+    // referenced elements can be rendered in a different viewport
+    test!(conv_percent_4,
+b"<svg viewBox='0 0 100 100'>
+    <rect id='rect1' width='50%'/>
+    <svg viewBox='0 0 10 10' width='50%'>
+        <use xlink:href='#rect1'/>
+    </svg>
+</svg>",
+"<svg viewBox='0 0 100 100'>
+    <rect id='rect1' width='50%'/>
+    <svg viewBox='0 0 10 10' width='50'>
+        <use xlink:href='#rect1'/>
+    </svg>
+</svg>
+");
+
+    // percentages in the inherited attributes are resolved against the nested viewport
+    test_eq!(keep_3,
+b"<svg viewBox='0 0 100 100'>
+    <g stroke-width='1%'>
+        <svg viewBox='0 0 10 10'/>
+    </g>
+</svg>
+");
+
+    // the 'font-size' is inherited from the 'use' element
+    test_eq!(keep_4,
+b"<svg>
+    <defs font-size='10'>
+        <rect id='rect1' height='1em' width='2em'/>
+    </defs>
+    <use font-size='20' xlink:href='#rect1'/>
+</svg>
+");
+
+    // the 'symbol' establishes a new viewport for the referenced element
+    test_eq!(keep_5,
+b"<svg viewBox='0 0 100 100'>
+    <defs>
+        <rect id='rect1' height='10' width='50%'/>
+    </defs>
+    <symbol id='s1' viewBox='0 0 20 20'>
+        <use xlink:href='#rect1'/>
+    </symbol>
+    <use xlink:href='#s1'/>
+</svg>
+");
+}
@@ -21,82 +21,249 @@
 ****************************************************************************/
 
 use super::short::{EId, AId, Unit};
+use super::ungroup_svg;
 
-use svgdom::{Document, AttributeValue};
+use svgdom::{Document, Node, AttributeType, AttributeValue};
 use svgdom::types::{Transform, Length};
 
 pub fn resolve_use(doc: &Document) {
-    let mut nodes = Vec::new();
+    // 'use' elements can be linked to other 'use' elements,
+    // so we are resolving them until nothing changes.
+    loop {
+        let mut nodes = Vec::new();
 
-    for node in doc.descendants().filter(|n| n.is_tag_name(EId::Use)) {
-        if let Some(value) = node.attribute_value(AId::XlinkHref) {
-            if let AttributeValue::Link(link) = value {
-
-                // resolve elements that linked to elements inside 'defs'
-                let parent = link.parent().unwrap();
-                if !parent.is_tag_name(EId::Defs) {
-                    continue;
+        for node in doc.descendants().filter(|n| n.is_tag_name(EId::Use)) {
+            if let Some(value) = node.attribute_value(AId::XlinkHref) {
+                if let AttributeValue::Link(link) = value {
+                    if let Some(data) = prepare_use(doc, &node, &link) {
+                        nodes.push((node.clone(), link.clone(), data));
+                    }
                 }
+            }
+        }
 
-                // 'symbol' elements can't be simply replaced
-                if link.is_tag_name(EId::Symbol) {
-                    continue;
-                }
+        if nodes.is_empty() {
+            break;
+        }
 
-                // ignore 'use' elements linked to other 'use' elements
-                if link.is_tag_name(EId::Use) {
-                    continue;
-                }
+        for (node, link, data) in nodes {
+            resolve(doc, &node, &link, data);
+        }
+    }
+}
 
-                // element should be used only once
-                if link.linked_nodes().count() != 1 {
-                    continue;
-                }
+struct UseData {
+    // 'use' transform with 'x' and 'y' applied
+    ts: Transform,
+    // 'symbol' viewport, if any
+    viewport: Option<ungroup_svg::Viewport>,
+}
 
-                nodes.push((node.clone(), link.clone()));
-            }
+fn prepare_use(doc: &Document, node: &Node, link: &Node) -> Option<UseData> {
+    // resolve elements that linked to elements inside 'defs'
+    let parent = link.parent().unwrap();
+    if !parent.is_tag_name(EId::Defs) {
+        return None;
+    }
+
+    // element should be used only once
+    if link.linked_nodes().count() != 1 {
+        return None;
+    }
+
+    // 'use' linked by other 'use' element will be resolved on the next iteration
+    if node.is_used() {
+        return None;
+    }
+
+    let attrs = node.attributes();
+
+    // 'use' element support 'x', 'y' and 'transform' attributes and we should process them
+    // so we apply translate transform to the linked element transform
+    let x = get_value!(attrs, Length, AId::X, Length::zero());
+    let y = get_value!(attrs, Length, AId::Y, Length::zero());
+
+    // we can apply 'x' and 'y' to transform only when they have 'none' units
+    if !(x.unit == Unit::None && y.unit == Unit::None) {
+        return None;
+    }
+
+    let mut ts = get_value!(attrs, Transform, AId::Transform, Transform::default());
+    ts.translate(x.num, y.num);
+
+    let viewport = if link.is_tag_name(EId::Symbol) {
+        Some(try_opt!(prepare_symbol_viewport(doc, node, link)))
+    } else {
+        None
+    };
+
+    // The 'use' clip path, mask and filter are resolved in the 'use' user space,
+    // which will be changed by the 'x', 'y' and linked element transforms.
+    if    attrs.contains(AId::ClipPath)
+       || attrs.contains(AId::Mask)
+       || attrs.contains(AId::Filter) {
+        let link_ts = {
+            let link_attrs = link.attributes();
+            get_value!(link_attrs, Transform, AId::Transform, Transform::default())
+        };
+
+        let vp_ts = match viewport {
+            Some(ref vp) => vp.ts,
+            None => Transform::default(),
+        };
+
+        if x.num != 0.0 || y.num != 0.0 || !link_ts.is_default() || !vp_ts.is_default() {
+            return None;
         }
     }
 
-    for (node, link) in nodes {
-        // unlink 'use'
-        node.remove_attribute(AId::XlinkHref);
+    // the 'use' and the linked element can't both have a clip path
+    let has_clip = match viewport {
+        Some(ref vp) => vp.clip.is_some(),
+        None => false,
+    };
+    if has_clip && attrs.contains(AId::ClipPath) {
+        return None;
+    }
+
+    // check that attributes can be merged
+    for (aid, attr) in attrs.iter_svg() {
+        if is_use_attribute(aid) || !link.has_attribute(aid) {
+            continue;
+        }
+
+        if aid == AId::Opacity || (attr.is_presentation() && attr.is_inheritable()) {
+            continue;
+        }
 
-        {
-            // 'use' element support 'x', 'y' and 'transform' attributes and we should process them
-            // so we apply translate transform to the linked element transform
+        return None;
+    }
 
-            let mut attrs = node.attributes_mut();
+    Some(UseData {
+        ts: ts,
+        viewport: viewport,
+    })
+}
 
-            // 'x' or 'y' should be set
-            if attrs.contains(AId::X) || attrs.contains(AId::Y) {
-                let x = get_value!(attrs, Length, AId::X, Length::zero());
-                let y = get_value!(attrs, Length, AId::Y, Length::zero());
+fn prepare_symbol_viewport(doc: &Document, node: &Node, symbol: &Node)
+                           -> Option<ungroup_svg::Viewport> {
+    let attrs = node.attributes();
 
-                // we can apply 'x' and 'y' to transform only when they have 'none' units
-                if !(x.unit == Unit::None && y.unit == Unit::None) {
-                    continue;
-                }
+    let def_size = Length::new(100.0, Unit::Percent);
+    let w = get_value!(attrs, Length, AId::Width, def_size);
+    let h = get_value!(attrs, Length, AId::Height, def_size);
 
-                let mut ts = get_value!(attrs, Transform, AId::Transform, Transform::default());
-                ts.translate(x.num, y.num);
+    // A 'symbol' without 'viewBox' and with a '100%' viewport, which is placed
+    // at the root viewport origin, will be clipped by the root viewport anyway.
+    if    w == def_size && h == def_size
+       && !symbol.has_attribute(AId::ViewBox)
+       && !attrs.contains(AId::X) && !attrs.contains(AId::Y)
+       && is_in_root_viewport(doc, node) {
+        return Some(ungroup_svg::Viewport {
+            ts: Transform::default(),
+            clip: None,
+        });
+    }
+
+    ungroup_svg::calc_viewport(symbol, Length::zero(), Length::zero(), w, h)
+}
+
+// Checks that the element's user space is the same as the root one.
+fn is_in_root_viewport(doc: &Document, node: &Node) -> bool {
+    // doc must contain 'svg' node, so we can safely unwrap
+    let svg = doc.svg_element().unwrap();
+
+    if node.has_attribute(AId::Transform) {
+        return false;
+    }
+
+    for p in node.parents() {
+        if p == svg {
+            return true;
+        }
+
+        if !p.is_tag_name(EId::G) || p.has_attribute(AId::Transform) {
+            return false;
+        }
+    }
+
+    false
+}
+
+// Attributes that are processed separately.
+fn is_use_attribute(aid: AId) -> bool {
+    match aid {
+          AId::XlinkHref
+        | AId::X
+        | AId::Y
+        | AId::Width
+        | AId::Height
+        | AId::Transform
+        | AId::Id => true,
+        _ => false,
+    }
+}
+
+fn resolve(doc: &Document, node: &Node, link: &Node, data: UseData) {
+    // unlink 'use'
+    node.remove_attribute(AId::XlinkHref);
 
-                attrs.insert_from(AId::Transform, ts);
-                attrs.remove(AId::X);
-                attrs.remove(AId::Y);
+    if let Some(ref vp) = data.viewport {
+        ungroup_svg::conv_to_group(doc, link, vp);
+    }
+
+    // the 'use' transform should be applied before the linked element transform
+    let mut ts = data.ts;
+    if let Some(AttributeValue::Transform(link_ts)) = link.attribute_value(AId::Transform) {
+        ts.append(&link_ts);
+    }
+
+    if ts.is_default() {
+        link.remove_attribute(AId::Transform);
+    } else {
+        link.set_attribute(AId::Transform, ts);
+    }
+
+    // copy attributes
+    for (aid, attr) in node.attributes().iter_svg() {
+        match aid {
+            AId::Width | AId::Height => {
+                // only 'svg' elements support them
+                if link.is_tag_name(EId::Svg) {
+                    link.set_attribute_object(attr.clone());
+                }
+                continue;
+            }
+            AId::Id => {
+                // keep the original 'id' if it's still referenced
+                if !link.is_used() {
+                    link.set_attribute_object(attr.clone());
+                }
+                continue;
             }
+            _ if is_use_attribute(aid) => continue,
+            _ => {}
         }
 
-        // copy attributes
-        for attr in node.attributes().iter() {
+        if aid == AId::Opacity && link.has_attribute(aid) {
+            // we can't just replace 'opacity' attribute,
+            // we should multiply it
+            let op1: f64 = *attr.value.as_number().unwrap();
+            let op2: f64 = *link.attribute_value(aid).unwrap().as_number().unwrap();
+            link.set_attribute(aid, op1 * op2);
+            continue;
+        }
+
+        // attributes of the linked element have a higher priority
+        if !link.has_attribute(aid) {
             link.set_attribute_object(attr.clone());
         }
+    }
 
-        // TODO: maybe just change the tag name
-        node.insert_after(&link);
+    // TODO: maybe just change the tag name
+    node.insert_after(link);
 
-        node.remove();
-    }
+    node.remove();
 }
 
 #[cfg(test)]
@@ -182,6 +349,88 @@ b"<svg>
     </defs>
     <rect id='r1' fill='url(#lg1)'/>
 </svg>
+");
+
+    test!(resolve_6,
+b"<svg>
+    <defs>
+        <rect id='r1' transform='scale(2)'/>
+    </defs>
+    <use transform='translate(10)' xlink:href='#r1'/>
+</svg>",
+"<svg>
+    <defs/>
+    <rect id='r1' transform='matrix(2 0 0 2 10 0)'/>
+</svg>
+");
+
+    test!(resolve_use_1,
+b"<svg>
+    <defs>
+        <rect id='r1' width='10'/>
+        <use id='u1' x='10' xlink:href='#r1'/>
+    </defs>
+    <use xlink:href='#u1' y='20'/>
+</svg>",
+"<svg>
+    <defs/>
+    <rect id='u1' transform='translate(10 20)' width='10'/>
+</svg>
+");
+
+    test!(resolve_symbol_1,
+b"<svg>
+    <defs>
+        <symbol id='s1'>
+            <rect/>
+        </symbol>
+    </defs>
+    <use xlink:href='#s1'/>
+</svg>",
+"<svg>
+    <defs/>
+    <g id='s1'>
+        <rect/>
+    </g>
+</svg>
+");
+
+    test!(resolve_symbol_2,
+b"<svg>
+    <defs>
+        <symbol id='s1' viewBox='0 0 10 10'>
+            <rect height='10' width='10'/>
+        </symbol>
+    </defs>
+    <use height='20' width='20' x='5' xlink:href='#s1'/>
+</svg>",
+"<svg>
+    <defs/>
+    <g id='s1' transform='matrix(2 0 0 2 5 0)'>
+        <rect height='10' width='10'/>
+    </g>
+</svg>
+");
+
+    test!(resolve_symbol_3,
+b"<svg>
+    <defs>
+        <symbol id='s1' viewBox='0 0 10 10'>
+            <rect height='20' width='20'/>
+        </symbol>
+    </defs>
+    <use height='20' width='20' xlink:href='#s1'/>
+</svg>",
+"<svg>
+    <defs>
+        <clipPath id='clipPath1'>
+            <rect height='10' width='10' x='0' y='0'/>
+        </clipPath>
+    </defs>
+    <g id='s1' clip-path='url(#clipPath1)' transform='scale(2)'>
+        <rect height='20' width='20'/>
+    </g>
+</svg>
 ");
 
     test_eq!(keep_1,
@@ -191,6 +440,32 @@ b"<svg>
 </svg>
 ");
 
+    // the clip path is defined in the 'use' user space
+    test_eq!(keep_clip_1,
+b"<svg>
+    <defs>
+        <clipPath id='clipPath1'>
+            <rect height='10' width='10'/>
+        </clipPath>
+        <rect id='r1' width='10'/>
+    </defs>
+    <use id='u1' x='10' xlink:href='#r1' clip-path='url(#clipPath1)'/>
+</svg>
+");
+
+    test_eq!(keep_mask_1,
+b"<svg>
+    <defs>
+        <mask id='mask1'>
+            <rect height='10' width='10'/>
+        </mask>
+        <rect id='r1' transform='scale(2)' width='10'/>
+    </defs>
+    <use id='u1' xlink:href='#r1' mask='url(#mask1)'/>
+</svg>
+");
+
+    // the viewport size depends on the root viewport
     test_eq!(keep_2,
 b"<svg>
     <defs>
@@ -198,7 +473,18 @@ b"<svg>
             <rect/>
         </symbol>
     </defs>
-    <use xlink:href='#r1'/>
+    <use transform='translate(10)' xlink:href='#r1'/>
+</svg>
+");
+
+    // the 'use' and the linked element can't both have a clip path
+    test_eq!(keep_3,
+b"<svg>
+    <defs>
+        <clipPath id='cp1'/>
+        <rect id='r1' clip-path='url(#cp1)'/>
+    </defs>
+    <use clip-path='url(#cp1)' xlink:href='#r1'/>
 </svg>
 ");
 }
@@ -0,0 +1,302 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+use task::short::{EId, AId};
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+
+use svgdom::{Document, Node};
+
+use options::DuplElements;
+
+pub fn remove_dupl_elements(doc: &Document, elements: &DuplElements) {
+    // Order is important, because elements are compared by links too.
+    // So we should start from elements that can be referenced by other ones.
+    let list = [
+        (EId::LinearGradient, elements.linear_gradient),
+        (EId::RadialGradient, elements.radial_gradient),
+        (EId::Filter, elements.filter),
+        (EId::ClipPath, elements.clip_path),
+        (EId::Mask, elements.mask),
+        (EId::Marker, elements.marker),
+        (EId::Pattern, elements.pattern),
+        (EId::Symbol, elements.symbol),
+    ];
+
+    for &(eid, flag) in list.iter() {
+        if !flag {
+            continue;
+        }
+
+        let nodes = doc.descendants().svg()
+                       .filter(|n| n.is_tag_name(eid))
+                       .collect::<Vec<Node>>();
+
+        super::rm_loop(nodes, hash_element, is_equal_elements);
+    }
+}
+
+// Compares elements structurally.
+//
+// Links are compared by the target identity.
+pub fn is_equal_elements(node1: &Node, node2: &Node) -> bool {
+    if !is_equal_attributes(node1, node2) {
+        return false;
+    }
+
+    is_equal_children(node1, node2)
+}
+
+fn hash_element(node: &Node) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    _hash_element(&mut hasher, node, node.tag_id());
+    hasher.finish()
+}
+
+fn _hash_element(hasher: &mut DefaultHasher, node: &Node, eid: Option<EId>) {
+    if let Some(eid) = node.tag_id() {
+        hasher.write(eid.name().as_bytes());
+    }
+
+    // attributes order doesn't matter during comparison, so we should sort them
+    let mut attrs: Vec<AId> = node.attributes().iter_svg()
+                                  .map(|(aid, _)| aid)
+                                  .filter(|aid| *aid != AId::Id)
+                                  .collect();
+    attrs.sort_by_key(|aid| aid.name());
+    super::hash_attrs(hasher, node, &attrs, eid);
+
+    hasher.write_usize(node.children().count());
+    for child in node.children().svg() {
+        _hash_element(hasher, &child, eid);
+    }
+}
+
+fn is_equal_attributes(node1: &Node, node2: &Node) -> bool {
+    if node1.tag_id() != node2.tag_id() {
+        return false;
+    }
+
+    let attrs1 = node1.attributes();
+    let attrs2 = node2.attributes();
+
+    let count1 = attrs1.iter().filter(|a| a.id() != Some(AId::Id)).count();
+    let count2 = attrs2.iter().filter(|a| a.id() != Some(AId::Id)).count();
+    if count1 != count2 {
+        return false;
+    }
+
+    for attr in attrs1.iter() {
+        if attr.id() == Some(AId::Id) {
+            continue;
+        }
+
+        match attr.id() {
+            Some(aid) => {
+                if attrs2.get_value(aid) != Some(&attr.value) {
+                    return false;
+                }
+            }
+            // we don't support non-SVG attributes
+            None => return false,
+        }
+    }
+
+    true
+}
+
+fn is_equal_children(node1: &Node, node2: &Node) -> bool {
+    // we support only SVG elements as children
+    let count1 = node1.children().count();
+    let count2 = node2.children().count();
+    if    count1 != count2
+       || count1 != node1.children().svg().count()
+       || count2 != node2.children().svg().count() {
+        return false;
+    }
+
+    for (c1, c2) in node1.children().zip(node2.children()) {
+        // we can't remove elements referenced by other elements
+        if c2.is_used() {
+            return false;
+        }
+
+        if !is_equal_elements(&c1, &c2) {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::{Document, WriteToString};
+
+    macro_rules! test {
+        ($name:ident, $in_text:expr, $out_text:expr) => (
+            #[test]
+            fn $name() {
+                let doc = Document::from_data($in_text).unwrap();
+                let elements = DuplElements {
+                    linear_gradient: true,
+                    radial_gradient: true,
+                    pattern: true,
+                    clip_path: true,
+                    mask: true,
+                    marker: true,
+                    symbol: true,
+                    filter: true,
+                };
+                remove_dupl_elements(&doc, &elements);
+                assert_eq_text!(doc.to_string_with_opt(&write_opt_for_tests!()), $out_text);
+            }
+        )
+    }
+
+    macro_rules! test_eq {
+        ($name:ident, $in_text:expr) => (
+            test!($name, $in_text, String::from_utf8_lossy($in_text));
+        )
+    }
+
+    test!(rm_clip_path_1,
+b"<svg>
+    <defs>
+        <clipPath id='cp1'>
+            <rect height='10' width='10'/>
+        </clipPath>
+        <clipPath id='cp2'>
+            <rect height='10' width='10'/>
+        </clipPath>
+    </defs>
+    <rect clip-path='url(#cp1)'/>
+    <rect clip-path='url(#cp2)'/>
+</svg>",
+"<svg>
+    <defs>
+        <clipPath id='cp1'>
+            <rect height='10' width='10'/>
+        </clipPath>
+    </defs>
+    <rect clip-path='url(#cp1)'/>
+    <rect clip-path='url(#cp1)'/>
+</svg>
+");
+
+    // patterns are equal after gradients deduplication
+    test!(rm_pattern_1,
+b"<svg>
+    <defs>
+        <linearGradient id='lg1'>
+            <stop offset='0'/>
+        </linearGradient>
+        <linearGradient id='lg2'>
+            <stop offset='0'/>
+        </linearGradient>
+        <pattern id='patt1' height='10' width='10'>
+            <rect fill='url(#lg1)' height='10' width='10'/>
+        </pattern>
+        <pattern id='patt2' height='10' width='10'>
+            <rect fill='url(#lg2)' height='10' width='10'/>
+        </pattern>
+    </defs>
+    <rect fill='url(#patt1)'/>
+    <rect fill='url(#patt2)'/>
+</svg>",
+"<svg>
+    <defs>
+        <linearGradient id='lg1'>
+            <stop offset='0'/>
+        </linearGradient>
+        <pattern id='patt1' height='10' width='10'>
+            <rect fill='url(#lg1)' height='10' width='10'/>
+        </pattern>
+    </defs>
+    <rect fill='url(#patt1)'/>
+    <rect fill='url(#patt1)'/>
+</svg>
+");
+
+    test!(rm_filter_1,
+b"<svg>
+    <defs>
+        <filter id='f1'>
+            <feGaussianBlur result='blur' stdDeviation='2'/>
+            <feOffset dx='1' in='blur'/>
+        </filter>
+        <filter id='f2'>
+            <feGaussianBlur result='blur' stdDeviation='2'/>
+            <feOffset dx='1' in='blur'/>
+        </filter>
+    </defs>
+    <rect filter='url(#f1)'/>
+    <rect filter='url(#f2)'/>
+</svg>",
+"<svg>
+    <defs>
+        <filter id='f1'>
+            <feGaussianBlur result='blur' stdDeviation='2'/>
+            <feOffset dx='1' in='blur'/>
+        </filter>
+    </defs>
+    <rect filter='url(#f1)'/>
+    <rect filter='url(#f1)'/>
+</svg>
+");
+
+    test_eq!(keep_1,
+b"<svg>
+    <defs>
+        <mask id='m1'>
+            <rect height='10' width='10'/>
+            <rect height='20' width='20'/>
+        </mask>
+        <mask id='m2'>
+            <rect height='20' width='20'/>
+            <rect height='10' width='10'/>
+        </mask>
+    </defs>
+    <rect mask='url(#m1)'/>
+    <rect mask='url(#m2)'/>
+</svg>
+");
+
+    // child element is referenced
+    test_eq!(keep_2,
+b"<svg>
+    <defs>
+        <clipPath id='cp1'>
+            <rect height='10' width='10'/>
+        </clipPath>
+        <clipPath id='cp2'>
+            <rect id='r1' height='10' width='10'/>
+        </clipPath>
+    </defs>
+    <rect clip-path='url(#cp1)'/>
+    <rect clip-path='url(#cp2)'/>
+    <use xlink:href='#r1'/>
+</svg>
+");
+}
@@ -22,6 +22,9 @@
 
 use task::short::{EId, AId};
 
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+
 use svgdom::{Document, Node};
 
 pub fn remove_dupl_fe_gaussian_blur(doc: &Document) {
@@ -73,7 +76,16 @@ pub fn remove_dupl_fe_gaussian_blur(doc: &Document) {
         nodes.push(node.clone());
     }
 
-    super::rm_loop(&mut nodes, |node1, node2| {
+    let hash = |node: &Node| {
+        let mut hasher = DefaultHasher::new();
+        super::hash_attrs(&mut hasher, node, &filter_attrs, node.tag_id());
+        // unwrap is safe, because we already checked that the filter has a child
+        super::hash_attrs(&mut hasher, &node.first_child().unwrap(), &fe_blur_attrs,
+                          node.tag_id());
+        hasher.finish()
+    };
+
+    super::rm_loop(nodes, hash, |node1, node2| {
         if !is_attrs_equal(node1, node2, &filter_attrs) {
             return false;
         }
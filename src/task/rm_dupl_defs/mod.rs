@@ -23,14 +23,20 @@
 pub use self::linear_gradient::remove_dupl_linear_gradients;
 pub use self::radial_gradient::remove_dupl_radial_gradients;
 pub use self::fe_gaussian_blur::remove_dupl_fe_gaussian_blur;
+pub use self::elements::remove_dupl_elements;
 
 mod linear_gradient;
 mod radial_gradient;
 mod fe_gaussian_blur;
+mod elements;
 
-use task::short::AId;
-use svgdom::types::{Transform};
-use svgdom::{Node, AttributeValue};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+
+use task::short::{EId, AId};
+use svgdom::types::{Length, Transform};
+use svgdom::{Node, AttributeValue, WriteOptions, WriteBuffer};
 
 macro_rules! check_attr {
     ($attrs1:expr, $attrs2:expr, $id:expr, $def:expr) => ({
@@ -42,7 +48,36 @@ macro_rules! check_attr {
     })
 }
 
-fn rm_loop<F>(nodes: &mut Vec<Node>, cmp: F)
+// Removes duplicated nodes.
+//
+// Comparing each pair of nodes is too slow on big files, so nodes are split into buckets
+// by the `hash` first and compared only within the same bucket.
+// The `hash` must return equal values for nodes that are equal according to `cmp`.
+fn rm_loop<H, F>(nodes: Vec<Node>, hash: H, cmp: F)
+    where H : Fn(&Node) -> u64,
+          F : Fn(&Node, &Node) -> bool
+{
+    // buckets are stored in the order of the first node appearance
+    let mut buckets: Vec<Vec<Node>> = Vec::new();
+    {
+        let mut map: HashMap<u64, usize> = HashMap::new();
+        for node in nodes {
+            let idx = *map.entry(hash(&node)).or_insert_with(|| {
+                buckets.push(Vec::new());
+                buckets.len() - 1
+            });
+            buckets[idx].push(node);
+        }
+    }
+
+    for mut bucket in buckets {
+        if bucket.len() > 1 {
+            rm_bucket(&mut bucket, &cmp);
+        }
+    }
+}
+
+fn rm_bucket<F>(nodes: &mut Vec<Node>, cmp: &F)
     where F : Fn(&Node, &Node) -> bool
 {
     let mut link_attrs: Vec<(Node, AId, Node)> = Vec::new();
@@ -100,6 +135,125 @@ fn rm_loop<F>(nodes: &mut Vec<Node>, cmp: F)
     }
 }
 
+// Hash of the attribute value.
+//
+// Values are hashed in the serialized form, because 'AttributeValue' doesn't implement 'Hash'.
+//
+// Numbers are compared fuzzily, so they are hashed in a rounded form.
+// Links to elements of the `eid` type can be changed after relinking,
+// so only the type of the linked element is hashed.
+fn hash_value(hasher: &mut DefaultHasher, value: Option<&AttributeValue>, eid: Option<EId>) {
+    let v = match value {
+        Some(v) => v,
+        None => {
+            hasher.write_u8(0);
+            return;
+        }
+    };
+
+    match *v {
+        AttributeValue::Number(n) => {
+            hasher.write_u8(2);
+            hash_number(hasher, n);
+        }
+        AttributeValue::NumberList(ref list) => {
+            hasher.write_u8(3);
+            hasher.write_usize(list.len());
+            for n in list {
+                hash_number(hasher, *n);
+            }
+        }
+        AttributeValue::Length(len) => {
+            hasher.write_u8(4);
+            hash_length(hasher, len);
+        }
+        AttributeValue::LengthList(ref list) => {
+            hasher.write_u8(5);
+            hasher.write_usize(list.len());
+            for len in list {
+                hash_length(hasher, *len);
+            }
+        }
+        AttributeValue::Transform(ts) => {
+            hasher.write_u8(6);
+            for n in &[ts.a, ts.b, ts.c, ts.d, ts.e, ts.f] {
+                hash_number(hasher, *n);
+            }
+        }
+        AttributeValue::Path(ref path) => {
+            hasher.write_u8(7);
+            hasher.write_usize(path.d.len());
+            for seg in &path.d {
+                hash_number(hasher, seg.x().unwrap_or(0.0));
+                hash_number(hasher, seg.y().unwrap_or(0.0));
+            }
+        }
+        AttributeValue::Link(ref n) | AttributeValue::FuncLink(ref n) => {
+            hasher.write_u8(8);
+            if n.tag_id() == eid {
+                hasher.write_u8(0);
+                if let Some(eid) = n.tag_id() {
+                    hasher.write(eid.name().as_bytes());
+                }
+            } else {
+                hasher.write_u8(1);
+                hasher.write(n.id().as_bytes());
+            }
+        }
+        _ => {
+            let mut buf = Vec::new();
+            v.write_buf_opt(&WriteOptions::default(), &mut buf);
+            hasher.write_u8(1);
+            hasher.write(&buf);
+        }
+    }
+}
+
+// Numbers that are equal according to 'FuzzyEq' should have the same hash,
+// so we round them to the comparison precision.
+fn hash_number(hasher: &mut DefaultHasher, n: f64) {
+    hasher.write_i64((n * 1000000.0).round() as i64);
+}
+
+fn hash_length(hasher: &mut DefaultHasher, len: Length) {
+    hash_number(hasher, len.num);
+    hasher.write_u8(len.unit as u8);
+}
+
+// `eid` is the type of the elements that are currently deduplicated.
+fn hash_attrs(hasher: &mut DefaultHasher, node: &Node, attrs: &[AId], eid: Option<EId>) {
+    let node_attrs = node.attributes();
+    for aid in attrs {
+        hash_value(hasher, node_attrs.get_value(*aid), eid);
+    }
+}
+
+fn hash_gradient(node: &Node, attrs: &[AId]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    hash_attrs(&mut hasher, node, attrs, node.tag_id());
+
+    {
+        let def = AttributeValue::from(Transform::default());
+        let node_attrs = node.attributes();
+        let ts = node_attrs.get_value(AId::GradientTransform).unwrap_or(&def);
+        hash_value(&mut hasher, Some(ts), node.tag_id());
+    }
+
+    hash_stops(&mut hasher, node);
+
+    hasher.finish()
+}
+
+pub fn hash_stops(hasher: &mut DefaultHasher, node: &Node) {
+    hasher.write_usize(node.children().count());
+
+    for child in node.children() {
+        hash_attrs(hasher, &child, &[AId::Offset, AId::StopColor, AId::StopOpacity],
+                   node.tag_id());
+    }
+}
+
 fn is_gradient_attrs_equal(node1: &Node, node2: &Node, attrs: &[AId]) -> bool {
     let attrs1 = node1.attributes();
     let attrs2 = node2.attributes();
@@ -151,8 +305,10 @@ pub fn is_equal_stops(node1: &Node, node2: &Node) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Instant;
+
     use svgdom::{Document, Node};
-    use task::short::EId;
+    use task::short::{EId, AId};
     use task::utils;
 
     macro_rules! test {
@@ -227,4 +383,73 @@ b"<svg>
         <stop offset='1'/>
     </linearGradient>
 </svg>", false);
+
+    #[test]
+    fn hash_numbers() {
+        let doc = Document::from_data(b"<svg>
+    <linearGradient x1='0.1'/>
+    <linearGradient x1='0.10000000000000001'/>
+    <linearGradient x1='0.2'/>
+</svg>").unwrap();
+        let hashes: Vec<u64> = doc.descendants().svg()
+                                  .filter(|n| n.is_tag_name(EId::LinearGradient))
+                                  .map(|n| super::hash_gradient(&n, &[AId::X1]))
+                                  .collect();
+
+        assert_eq!(hashes[0], hashes[1]);
+        assert!(hashes[0] != hashes[2]);
+    }
+
+    // Benchmark fixture with a lot of gradients, like in Illustrator exports.
+    // Gradients have the same colors and differ only by numbers.
+    // Run with `cargo test --release -- --ignored --nocapture`.
+    fn gen_gradients(count: usize) -> Document {
+        let mut text = String::from("<svg><defs>");
+        for i in 0..count {
+            // each gradient has exactly one duplicate
+            text.push_str(&format!(
+                "<linearGradient id='lg{}' x1='{}'>\
+                    <stop offset='{}'/>\
+                    <stop offset='1'/>\
+                 </linearGradient>", i, i / 2, (i / 2) as f64 / count as f64));
+        }
+        text.push_str("</defs></svg>");
+
+        let doc = Document::from_data(text.as_bytes()).unwrap();
+        utils::resolve_gradient_attributes(&doc).unwrap();
+        doc
+    }
+
+    fn count_gradients(doc: &Document) -> usize {
+        doc.descendants().svg().filter(|n| n.is_tag_name(EId::LinearGradient)).count()
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_rm_dupl_linear_gradients() {
+        let doc = gen_gradients(10000);
+
+        let now = Instant::now();
+        super::remove_dupl_linear_gradients(&doc);
+        let elapsed = now.elapsed();
+
+        println!("remove_dupl_linear_gradients: {}.{:03}s",
+                 elapsed.as_secs(), elapsed.subsec_nanos() / 1000000);
+        assert_eq!(count_gradients(&doc), 5000);
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_regroup_gradient_stops() {
+        let doc = gen_gradients(10000);
+
+        let now = Instant::now();
+        ::task::regroup_gradient_stops(&doc);
+        let elapsed = now.elapsed();
+
+        println!("regroup_gradient_stops: {}.{:03}s",
+                 elapsed.as_secs(), elapsed.subsec_nanos() / 1000000);
+        // a new gradient is created for each pair
+        assert_eq!(count_gradients(&doc), 15000);
+    }
 }
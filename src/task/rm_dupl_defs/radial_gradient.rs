@@ -35,11 +35,11 @@ pub fn remove_dupl_radial_gradients(doc: &Document) {
         AId::SpreadMethod,
     ];
 
-    let mut nodes = doc.descendants().svg()
+    let nodes = doc.descendants().svg()
                        .filter(|n| n.is_tag_name(EId::RadialGradient))
                        .collect::<Vec<Node>>();
 
-    super::rm_loop(&mut nodes, |node1, node2| {
+    super::rm_loop(nodes, |node| super::hash_gradient(node, &attrs), |node1, node2| {
         if !super::is_gradient_attrs_equal(node1, node2, &attrs) {
             return false;
         }
@@ -20,16 +20,295 @@
 **
 ****************************************************************************/
 
-use super::short::AId;
+use super::short::{EId, AId, Unit};
+use geom::{self, Rect};
 
-use svgdom::{Document, Node, AttributeValue, ValueId};
+use svgdom::{Document, Node, AttributeValue, ValueId, WriteOptions, WriteBuffer};
+use svgdom::types::{Length, Transform, FuzzyEq};
 
-pub fn remove_gradient_attributes(doc: &Document) {
-    // TODO: process coordinates and transform
-    //       we will get a small impact on a cleaning ratio, so keep it for the next release
+pub fn remove_gradient_attributes(doc: &Document, opt: &WriteOptions) {
+    process_coordinates(doc, opt);
     process_units(doc);
 }
 
+static LINEAR_ATTRS: &'static [AId] = &[AId::X1, AId::Y1, AId::X2, AId::Y2];
+static RADIAL_ATTRS: &'static [AId] = &[AId::Cx, AId::Cy, AId::Fx, AId::Fy, AId::R];
+
+// Gradient geometry in the specified units.
+#[derive(Clone)]
+struct Geometry {
+    units: ValueId,
+    ts: Transform,
+    // values of the LINEAR_ATTRS or RADIAL_ATTRS
+    coords: Vec<f64>,
+}
+
+// A gradient that is used only by a single shape with a known bounding box
+// can be converted between 'objectBoundingBox' and 'userSpaceOnUse' units.
+// Also, 'gradientTransform' can be applied to the coordinates in some cases.
+//
+// We are choosing the shortest variant.
+fn process_coordinates(doc: &Document, opt: &WriteOptions) {
+    let iter = doc.descendants().svg()
+                  .filter(|n| super::is_gradient(n))
+                  .filter(|n| !n.has_attribute(AId::XlinkHref));
+
+    for node in iter {
+        let bbox = match get_user_bbox(&node) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let is_linear = node.is_tag_name(EId::LinearGradient);
+
+        let curr = match get_geometry(&node, is_linear) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let mut list = vec![curr.clone()];
+        list.push(conv_units(&curr, &bbox));
+        for i in 0..list.len() {
+            if let Some(g) = apply_transform(&list[i], is_linear) {
+                list.push(g);
+            }
+        }
+
+        let curr_len = geometry_len(&curr, is_linear, opt);
+        let mut best: Option<(usize, &Geometry)> = None;
+        for g in list.iter().skip(1) {
+            let len = geometry_len(g, is_linear, opt);
+            let is_shorter = match best {
+                Some((l, _)) => len < l,
+                None => len < curr_len,
+            };
+
+            if is_shorter {
+                best = Some((len, g));
+            }
+        }
+
+        if let Some((_, g)) = best {
+            set_geometry(&node, g, is_linear, opt);
+        }
+    }
+}
+
+// Returns a bounding box of the shape that uses the gradient.
+fn get_user_bbox(node: &Node) -> Option<Rect> {
+    // the gradient must be used only by a single shape and not by other gradients
+    let mut links = node.linked_nodes();
+    let shape = try_opt!(links.next());
+    if links.any(|n| n != shape) {
+        return None;
+    }
+
+    geom::calc_shape_bbox(&shape)
+}
+
+fn get_geometry(node: &Node, is_linear: bool) -> Option<Geometry> {
+    let attrs = node.attributes();
+
+    let units = match attrs.get_value(AId::GradientUnits) {
+        Some(&AttributeValue::PredefValue(ValueId::UserSpaceOnUse)) => ValueId::UserSpaceOnUse,
+        _ => ValueId::ObjectBoundingBox,
+    };
+
+    let ts = get_value!(attrs, Transform, AId::GradientTransform, Transform::default());
+
+    let aids = if is_linear { LINEAR_ATTRS } else { RADIAL_ATTRS };
+
+    let mut coords = Vec::with_capacity(aids.len());
+    for (i, aid) in aids.iter().enumerate() {
+        let def = if is_linear {
+            if *aid == AId::X2 { Length::new(100.0, Unit::Percent) } else { Length::zero() }
+        } else {
+            match *aid {
+                // 'fx' and 'fy' are equal to 'cx' and 'cy' by default
+                AId::Fx | AId::Fy => Length::new(coords[i - 2], Unit::None),
+                _ => Length::new(50.0, Unit::Percent),
+            }
+        };
+
+        let len = get_value!(attrs, Length, *aid, def);
+        let n = match len.unit {
+            Unit::None => len.num,
+            // percentages in the 'userSpaceOnUse' units are relative to the viewport
+            Unit::Percent if units == ValueId::ObjectBoundingBox => len.num / 100.0,
+            _ => return None,
+        };
+        coords.push(n);
+    }
+
+    Some(Geometry { units: units, ts: ts, coords: coords })
+}
+
+// Converts gradient units by moving the bounding box into the gradient transform.
+fn conv_units(g: &Geometry, bbox: &Rect) -> Geometry {
+    let bbox_ts = Transform::new(bbox.x2 - bbox.x1, 0.0, 0.0, bbox.y2 - bbox.y1, bbox.x1, bbox.y1);
+
+    let (units, mut ts) = if g.units == ValueId::ObjectBoundingBox {
+        (ValueId::UserSpaceOnUse, bbox_ts)
+    } else {
+        // unwrap is safe, because the bbox size is not zero
+        (ValueId::ObjectBoundingBox, invert_ts(&bbox_ts).unwrap())
+    };
+    ts.append(&g.ts);
+
+    Geometry { units: units, ts: ts, coords: g.coords.clone() }
+}
+
+// Applies the gradient transform to the gradient coordinates.
+fn apply_transform(g: &Geometry, is_linear: bool) -> Option<Geometry> {
+    let ts = g.ts;
+    if ts.is_default() {
+        return None;
+    }
+
+    let mut coords = g.coords.clone();
+
+    if is_linear {
+        let ts_inv = try_opt!(invert_ts(&ts));
+
+        // Gradient stripes are perpendicular to the gradient vector, so the vector
+        // should be transformed by the inverse transpose of the transform to stay
+        // perpendicular to them.
+        let vx = coords[2] - coords[0];
+        let vy = coords[3] - coords[1];
+        let nx = ts_inv.a * vx + ts_inv.b * vy;
+        let ny = ts_inv.c * vx + ts_inv.d * vy;
+
+        let nlen = nx * nx + ny * ny;
+        if nlen.fuzzy_eq(&0.0) {
+            return None;
+        }
+
+        // the end point must stay on the same stripe
+        let k = (vx * vx + vy * vy) / nlen;
+
+        let (x1, y1) = ts.apply(coords[0], coords[1]);
+        coords[0] = x1;
+        coords[1] = y1;
+        coords[2] = x1 + nx * k;
+        coords[3] = y1 + ny * k;
+    } else {
+        // a circle stays a circle only after translate, rotate and proportional scale
+        if !(ts.a.fuzzy_eq(&ts.d) && ts.b.fuzzy_eq(&-ts.c)) {
+            return None;
+        }
+
+        let (cx, cy) = ts.apply(coords[0], coords[1]);
+        let (fx, fy) = ts.apply(coords[2], coords[3]);
+        coords[0] = cx;
+        coords[1] = cy;
+        coords[2] = fx;
+        coords[3] = fy;
+        coords[4] *= (ts.a * ts.a + ts.b * ts.b).sqrt();
+    }
+
+    Some(Geometry { units: g.units, ts: Transform::default(), coords: coords })
+}
+
+fn invert_ts(ts: &Transform) -> Option<Transform> {
+    let det = ts.a * ts.d - ts.b * ts.c;
+    if det.fuzzy_eq(&0.0) {
+        return None;
+    }
+
+    let a = ts.d / det;
+    let b = -ts.b / det;
+    let c = -ts.c / det;
+    let d = ts.a / det;
+    let e = -(a * ts.e + c * ts.f);
+    let f = -(b * ts.e + d * ts.f);
+
+    Some(Transform::new(a, b, c, d, e, f))
+}
+
+// Checks that coordinate is equal to the default value.
+//
+// Default values are percentages, so they can be omitted
+// only in the 'objectBoundingBox' units.
+//
+// Numbers are compared in the serialized form, because calculated coordinates
+// are usually slightly different from the default ones.
+fn is_default_coord(g: &Geometry, is_linear: bool, idx: usize, opt: &WriteOptions) -> bool {
+    let is_bbox = g.units == ValueId::ObjectBoundingBox;
+    let n = num_to_vec(g.coords[idx], opt);
+    let is_eq = |v: f64| n == num_to_vec(v, opt);
+
+    if is_linear {
+        match idx {
+            2 => is_bbox && is_eq(1.0),
+            _ => is_eq(0.0),
+        }
+    } else {
+        match idx {
+            2 | 3 => is_eq(g.coords[idx - 2]),
+            _ => is_bbox && is_eq(0.5),
+        }
+    }
+}
+
+fn num_to_vec(n: f64, opt: &WriteOptions) -> Vec<u8> {
+    let mut buf = Vec::new();
+    AttributeValue::Number(n).write_buf_opt(opt, &mut buf);
+    buf
+}
+
+// Returns the length of the serialized gradient geometry attributes.
+fn geometry_len(g: &Geometry, is_linear: bool, opt: &WriteOptions) -> usize {
+    // name + ="" + space
+    fn attr_len(aid: AId, value: AttributeValue, opt: &WriteOptions) -> usize {
+        let mut buf = Vec::new();
+        value.write_buf_opt(opt, &mut buf);
+        aid.name().len() + buf.len() + 4
+    }
+
+    let mut len = 0;
+
+    if g.units == ValueId::UserSpaceOnUse {
+        len += attr_len(AId::GradientUnits, AttributeValue::PredefValue(g.units), opt);
+    }
+
+    if !g.ts.is_default() {
+        len += attr_len(AId::GradientTransform, AttributeValue::Transform(g.ts), opt);
+    }
+
+    let aids = if is_linear { LINEAR_ATTRS } else { RADIAL_ATTRS };
+    for (i, aid) in aids.iter().enumerate() {
+        if !is_default_coord(g, is_linear, i, opt) {
+            let value = AttributeValue::Length(Length::new(g.coords[i], Unit::None));
+            len += attr_len(*aid, value, opt);
+        }
+    }
+
+    len
+}
+
+fn set_geometry(node: &Node, g: &Geometry, is_linear: bool, opt: &WriteOptions) {
+    if g.units == ValueId::UserSpaceOnUse {
+        node.set_attribute(AId::GradientUnits, g.units);
+    } else {
+        node.remove_attribute(AId::GradientUnits);
+    }
+
+    if g.ts.is_default() {
+        node.remove_attribute(AId::GradientTransform);
+    } else {
+        node.set_attribute(AId::GradientTransform, g.ts);
+    }
+
+    let aids = if is_linear { LINEAR_ATTRS } else { RADIAL_ATTRS };
+    for (i, aid) in aids.iter().enumerate() {
+        if is_default_coord(g, is_linear, i, opt) {
+            node.remove_attribute(*aid);
+        } else {
+            node.set_attribute(*aid, (g.coords[i], Unit::None));
+        }
+    }
+}
+
 fn process_units(doc: &Document) {
     rm_equal(doc);
     group_to_parent(doc);
@@ -179,7 +458,7 @@ mod tests {
             fn $name() {
                 let doc = Document::from_data($in_text).unwrap();
                 utils::resolve_gradient_attributes(&doc).unwrap();
-                remove_gradient_attributes(&doc);
+                remove_gradient_attributes(&doc, &write_opt_for_tests!());
                 assert_eq_text!(doc.to_string_with_opt(&write_opt_for_tests!()), $out_text);
             }
         )
@@ -277,6 +556,67 @@ b"<svg>
     <linearGradient xlink:href='#lg1'/>
     <linearGradient gradientUnits='objectBoundingBox' xlink:href='#lg1'/>
 </svg>
+");
+
+    test!(conv_units_1,
+b"<svg>
+    <linearGradient id='lg1' gradientUnits='userSpaceOnUse' x1='10' x2='60' y1='20' y2='70'/>
+    <rect fill='url(#lg1)' height='50' width='50' x='10' y='20'/>
+</svg>",
+"<svg>
+    <linearGradient id='lg1' y2='1'/>
+    <rect fill='url(#lg1)' height='50' width='50' x='10' y='20'/>
+</svg>
+");
+
+    test!(conv_units_2,
+b"<svg>
+    <radialGradient id='rg1' cx='5' cy='5' gradientTransform='translate(10 20)' gradientUnits='userSpaceOnUse' r='5'/>
+    <rect fill='url(#rg1)' height='100' width='100'/>
+</svg>",
+"<svg>
+    <radialGradient id='rg1' cx='0.15' cy='0.25' r='0.05'/>
+    <rect fill='url(#rg1)' height='100' width='100'/>
+</svg>
+");
+
+    // curves have an exact bbox too
+    test!(conv_units_3,
+b"<svg>
+    <linearGradient id='lg1' gradientUnits='userSpaceOnUse' x1='10' x2='60' y1='20' y2='70'/>
+    <path d='M 10 20 C 10 20 60 70 60 70' fill='url(#lg1)'/>
+</svg>",
+"<svg>
+    <linearGradient id='lg1' y2='1'/>
+    <path d='M 10 20 C 10 20 60 70 60 70' fill='url(#lg1)'/>
+</svg>
+");
+
+    test!(apply_transform_1,
+b"<svg>
+    <linearGradient id='lg1' gradientTransform='matrix(1 0 0 2 0 0)' gradientUnits='userSpaceOnUse' x1='10' x2='10' y1='10' y2='20'/>
+    <rect fill='url(#lg1)' height='91' width='37'/>
+</svg>",
+"<svg>
+    <linearGradient id='lg1' gradientUnits='userSpaceOnUse' x1='10' x2='10' y1='20' y2='40'/>
+    <rect fill='url(#lg1)' height='91' width='37'/>
+</svg>
+");
+
+    test_eq!(keep_conv_units_1,
+b"<svg>
+    <linearGradient id='lg1' gradientUnits='userSpaceOnUse' x1='10' x2='60' y1='20' y2='70'/>
+    <rect fill='url(#lg1)' height='50' width='50' x='10' y='20'/>
+    <rect fill='url(#lg1)' height='50' width='50' x='10' y='20'/>
+</svg>
+");
+
+    // the text bbox is not exact
+    test_eq!(keep_conv_units_2,
+b"<svg>
+    <linearGradient id='lg1' gradientUnits='userSpaceOnUse' x1='10' x2='60' y1='20' y2='70'/>
+    <text fill='url(#lg1)'>Text</text>
+</svg>
 ");
 
 }
@@ -22,7 +22,10 @@
 
 use task::short::AId;
 
-use svgdom::Document;
+use geom;
+
+use svgdom::{Document, Node, AttributeValue, ValueId};
+use svgdom::types::{Color, FuzzyEq};
 
 pub fn remove_invalid_stops(doc: &Document) {
     let mut nodes = Vec::new();
@@ -53,6 +56,176 @@ pub fn remove_invalid_stops(doc: &Document) {
     for n in nodes {
         n.remove();
     }
+
+    let iter = doc.descendants().svg()
+                  .filter(|n| super::is_gradient(n))
+                  .filter(|n| n.has_children());
+    for node in iter {
+        rm_equal_offset_stops(&node);
+        rm_interpolated_stops(&node);
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Stop {
+    offset: f64,
+    color: Color,
+    opacity: f64,
+}
+
+// Returns `None` if the stop attributes are not resolved.
+//
+// 'stop-color' and 'stop-opacity' are not inheritable, so we can use default values.
+fn get_stop(node: &Node) -> Option<Stop> {
+    let attrs = node.attributes();
+
+    let offset = match attrs.get_value(AId::Offset) {
+        Some(&AttributeValue::Number(n)) => n,
+        _ => return None,
+    };
+
+    let color = match attrs.get_value(AId::StopColor) {
+        Some(&AttributeValue::Color(c)) => c,
+        Some(_) => return None,
+        None => Color { red: 0, green: 0, blue: 0 },
+    };
+
+    let opacity = match attrs.get_value(AId::StopOpacity) {
+        Some(&AttributeValue::Number(n)) => n,
+        Some(_) => return None,
+        None => 1.0,
+    };
+
+    Some(Stop {
+        offset: offset,
+        color: color,
+        opacity: opacity,
+    })
+}
+
+// Returns `None` if any stop attributes are not resolved.
+//
+// An offset, which is less than any previous one, is rendered as the largest previous offset,
+// so offsets are clamped accordingly.
+fn get_stops(node: &Node) -> Option<Vec<(Node, Stop)>> {
+    let mut stops: Vec<(Node, Stop)> = Vec::new();
+    for child in node.children() {
+        let mut stop = try_opt!(get_stop(&child));
+
+        if let Some(&(_, prev)) = stops.last() {
+            if stop.offset < prev.offset {
+                stop.offset = prev.offset;
+            }
+        }
+
+        stops.push((child.clone(), stop));
+    }
+
+    Some(stops)
+}
+
+// If more than two stops have the same offset, then only the first and the last one
+// will be rendered. The first one ends the previous color transition
+// and the last one starts the next.
+fn rm_equal_offset_stops(node: &Node) {
+    let stops = match get_stops(node) {
+        Some(stops) => stops,
+        None => return,
+    };
+
+    let mut rm_list = Vec::new();
+    let mut i = 0;
+    while i < stops.len() {
+        let offset = stops[i].1.offset;
+
+        let mut j = i + 1;
+        while j < stops.len() && stops[j].1.offset.fuzzy_eq(&offset) {
+            j += 1;
+        }
+
+        // remove stops between the first and the last one
+        if j - i > 2 {
+            rm_list.extend(stops[(i + 1)..(j - 1)].iter().map(|&(ref n, _)| n.clone()));
+        }
+
+        i = j;
+    }
+
+    for n in rm_list {
+        n.remove();
+    }
+}
+
+// Removes stops, which color and opacity are linear interpolations of their neighbours.
+fn rm_interpolated_stops(node: &Node) {
+    // colors are checked in the sRGB color space
+    if !is_srgb_interpolation(node) {
+        return;
+    }
+
+    let stops = match get_stops(node) {
+        Some(stops) => stops,
+        None => return,
+    };
+
+    if stops.len() < 3 {
+        return;
+    }
+
+    let mut rm_list = Vec::new();
+    // the last kept stop
+    let mut prev = stops[0].1;
+    // stops removed since the last kept one
+    let mut skipped: Vec<Stop> = Vec::new();
+    for i in 1..(stops.len() - 1) {
+        let curr = stops[i].1;
+        let next = stops[i + 1].1;
+
+        // all previously removed stops must be interpolations of the new pair too
+        let is_interpolated =    is_interpolated(&prev, &next, &curr)
+                              && skipped.iter().all(|s| is_interpolated(&prev, &next, s));
+
+        if is_interpolated {
+            rm_list.push(stops[i].0.clone());
+            skipped.push(curr);
+        } else {
+            prev = curr;
+            skipped.clear();
+        }
+    }
+
+    for n in rm_list {
+        n.remove();
+    }
+}
+
+// 'color-interpolation' is inheritable and is 'sRGB' by default.
+fn is_srgb_interpolation(node: &Node) -> bool {
+    match geom::resolve_inherited(node, AId::ColorInterpolation) {
+          Some(AttributeValue::PredefValue(ValueId::SRGB))
+        | Some(AttributeValue::PredefValue(ValueId::Auto))
+        | None => true,
+        _ => false,
+    }
+}
+
+fn is_interpolated(prev: &Stop, next: &Stop, stop: &Stop) -> bool {
+    // stops with equal offsets are sharp color transitions
+    if !(prev.offset < stop.offset && stop.offset < next.offset) {
+        return false;
+    }
+
+    let t = (stop.offset - prev.offset) / (next.offset - prev.offset);
+
+    let lerp = |a: f64, b: f64| a + (b - a) * t;
+
+    // colors are stored as integers, so we allow a rounding error
+    let is_color_eq = |a: u8, b: u8, c: u8| (lerp(a as f64, b as f64) - c as f64).abs() <= 0.5;
+
+       is_color_eq(prev.color.red, next.color.red, stop.color.red)
+    && is_color_eq(prev.color.green, next.color.green, stop.color.green)
+    && is_color_eq(prev.color.blue, next.color.blue, stop.color.blue)
+    && lerp(prev.opacity, next.opacity).fuzzy_eq(&stop.opacity)
 }
 
 #[cfg(test)]
@@ -163,4 +336,135 @@ b"<svg>
 </svg>
 ");
 
+    test!(rm_equal_offsets_1,
+b"<svg>
+    <linearGradient>
+        <stop offset='0'/>
+        <stop offset='0.5' stop-color='#ff0000'/>
+        <stop offset='0.5' stop-color='#00ff00'/>
+        <stop offset='0.5' stop-color='#0000ff'/>
+        <stop offset='1'/>
+    </linearGradient>
+</svg>",
+"<svg>
+    <linearGradient>
+        <stop offset='0'/>
+        <stop offset='0.5' stop-color='#ff0000'/>
+        <stop offset='0.5' stop-color='#0000ff'/>
+        <stop offset='1'/>
+    </linearGradient>
+</svg>
+");
+
+    // a decreasing offset is clamped to the largest previous one
+    test!(rm_equal_offsets_2,
+b"<svg>
+    <linearGradient>
+        <stop offset='0'/>
+        <stop offset='0.5' stop-color='#ff0000'/>
+        <stop offset='0.3' stop-color='#00ff00'/>
+        <stop offset='0.5' stop-color='#0000ff'/>
+        <stop offset='1'/>
+    </linearGradient>
+</svg>",
+"<svg>
+    <linearGradient>
+        <stop offset='0'/>
+        <stop offset='0.5' stop-color='#ff0000'/>
+        <stop offset='0.5' stop-color='#0000ff'/>
+        <stop offset='1'/>
+    </linearGradient>
+</svg>
+");
+
+    test!(rm_interpolated_1,
+b"<svg>
+    <linearGradient>
+        <stop offset='0' stop-color='#000000'/>
+        <stop offset='0.5' stop-color='#808080'/>
+        <stop offset='1' stop-color='#ffffff'/>
+    </linearGradient>
+</svg>",
+"<svg>
+    <linearGradient>
+        <stop offset='0' stop-color='#000000'/>
+        <stop offset='1' stop-color='#ffffff'/>
+    </linearGradient>
+</svg>
+");
+
+    test!(rm_interpolated_2,
+b"<svg>
+    <linearGradient>
+        <stop offset='0' stop-color='#ff0000'/>
+        <stop offset='0.3' stop-color='#ff0000'/>
+        <stop offset='0.6' stop-color='#ff0000'/>
+        <stop offset='1' stop-color='#0000ff'/>
+    </linearGradient>
+</svg>",
+"<svg>
+    <linearGradient>
+        <stop offset='0' stop-color='#ff0000'/>
+        <stop offset='0.6' stop-color='#ff0000'/>
+        <stop offset='1' stop-color='#0000ff'/>
+    </linearGradient>
+</svg>
+");
+
+    test!(rm_interpolated_3,
+b"<svg>
+    <linearGradient>
+        <stop offset='0' stop-opacity='0'/>
+        <stop offset='0.25' stop-opacity='0.25'/>
+        <stop offset='0.5' stop-opacity='0.5'/>
+        <stop offset='1' stop-opacity='1'/>
+    </linearGradient>
+</svg>",
+"<svg>
+    <linearGradient>
+        <stop offset='0' stop-opacity='0'/>
+        <stop offset='1' stop-opacity='1'/>
+    </linearGradient>
+</svg>
+");
+
+    test_eq!(keep_interpolated_1,
+b"<svg>
+    <linearGradient>
+        <stop offset='0'/>
+        <stop offset='0.5' stop-color='#ff0000'/>
+        <stop offset='1'/>
+    </linearGradient>
+</svg>
+");
+
+    // colors are interpolated in the linearRGB color space
+    test_eq!(keep_interpolated_2,
+b"<svg>
+    <linearGradient color-interpolation='linearRGB'>
+        <stop offset='0' stop-color='#000000'/>
+        <stop offset='0.5' stop-color='#808080'/>
+        <stop offset='1' stop-color='#ffffff'/>
+    </linearGradient>
+</svg>
+");
+
+    // all removed stops should be checked against the new neighbours
+    test!(rm_interpolated_4,
+b"<svg>
+    <linearGradient>
+        <stop offset='0' stop-color='#000000'/>
+        <stop offset='0.25' stop-color='#3f3f3f'/>
+        <stop offset='0.5' stop-color='#7f7f7f'/>
+        <stop offset='1' stop-color='#ffffff'/>
+    </linearGradient>
+</svg>",
+"<svg>
+    <linearGradient>
+        <stop offset='0' stop-color='#000000'/>
+        <stop offset='0.5' stop-color='#7f7f7f'/>
+        <stop offset='1' stop-color='#ffffff'/>
+    </linearGradient>
+</svg>
+");
 }
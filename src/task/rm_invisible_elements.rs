@@ -20,28 +20,41 @@
 **
 ****************************************************************************/
 
-use super::short::{EId, AId};
+use super::short::{EId, AId, Unit};
+use geom::{self, Rect};
 
-use svgdom::{Document, Node, ElementType, AttributeValue, ValueId};
-use svgdom::types::FuzzyEq;
+use options::Options;
 
-// TODO: process mask element
-// TODO: process visibility
-// TODO: process feGaussianBlur with stdDeviation=0
-// TODO: split to suboptions
-// TODO: polyline/polygon without points
-// TODO: remove elements with transform="matrix(0 0 0 0 0 0)"
+use svgdom::{Document, Node, ElementType, AttributeValue, ValueId};
+use svgdom::types::{Color, Length, Transform, FuzzyEq};
 
-pub fn remove_invisible_elements(doc: &Document) {
+pub fn remove_invisible_elements(doc: &Document, options: &Options) {
     let mut is_any_removed = false;
-    process_display_attribute(doc, &mut is_any_removed);
-    process_paths(doc, &mut is_any_removed);
+
+    if options.remove_hidden_elements {
+        process_display_attribute(doc, &mut is_any_removed);
+        process_visibility(doc, &mut is_any_removed);
+        process_opacity(doc, &mut is_any_removed);
+        process_invalid_transform(doc, &mut is_any_removed);
+    }
+
+    if options.remove_unpainted_elements {
+        process_unpainted(doc, &mut is_any_removed);
+    }
+
+    if options.remove_empty_shapes {
+        process_paths(doc, &mut is_any_removed);
+        process_polylines(doc, &mut is_any_removed);
+        process_use(doc, &mut is_any_removed);
+        process_rect(doc, &mut is_any_removed);
+    }
+
     process_clip_paths(doc, &mut is_any_removed);
+    process_masks(doc, &mut is_any_removed);
+    process_unclipped_elements(doc, &mut is_any_removed);
     process_empty_filter(doc, &mut is_any_removed);
     process_fe_color_matrix(doc);
-    process_use(doc, &mut is_any_removed);
     process_gradients(doc, &mut is_any_removed);
-    process_rect(doc, &mut is_any_removed);
 
     if is_any_removed {
         super::remove_unused_defs(doc);
@@ -106,6 +119,313 @@ fn is_valid_clip_path_elem(node: &Node) -> bool {
     is_valid_shape(node)
 }
 
+// Removes empty masks and masks that don't affect the rendering.
+// Masks with a single white shape are converted into clip paths.
+fn process_masks(doc: &Document, is_any_removed: &mut bool) {
+    let masks: Vec<Node> = doc.descendants().svg().filter(|n| n.is_tag_name(EId::Mask)).collect();
+
+    for mask in masks {
+        if !mask.has_children() {
+            // An empty mask makes all elements that use it invisible,
+            // so we can remove them as well.
+            for link in mask.linked_nodes().collect::<Vec<Node>>() {
+                link.remove();
+            }
+            mask.remove();
+            *is_any_removed = true;
+            continue;
+        }
+
+        if !is_simple_mask(&mask) {
+            continue;
+        }
+
+        // unwrap is safe, because a simple mask has a single child
+        let child = mask.first_child().unwrap();
+
+        if is_covering_mask(&mask, &child) {
+            for link in mask.linked_nodes().collect::<Vec<Node>>() {
+                link.remove_attribute(AId::Mask);
+            }
+            mask.remove();
+            *is_any_removed = true;
+        } else {
+            conv_mask_to_clip_path(&mask, &child);
+        }
+    }
+}
+
+// Removes 'clip-path' attributes from elements that are entirely inside the clip region.
+//
+// Clip paths that became unused will be removed by the 'remove_unused_defs'.
+fn process_unclipped_elements(doc: &Document, is_any_removed: &mut bool) {
+    let clip_paths: Vec<Node> = doc.descendants().svg()
+        .filter(|n| n.is_tag_name(EId::ClipPath))
+        .collect();
+
+    for clip_path in clip_paths {
+        // the clip region can be reduced by the nested clip path
+        if clip_path.has_attribute(AId::ClipPath) {
+            continue;
+        }
+
+        let nodes: Vec<Node> = clip_path.linked_nodes().filter(|n| {
+            match n.attribute_value(AId::ClipPath) {
+                Some(AttributeValue::FuncLink(ref link)) => *link == clip_path,
+                _ => false,
+            }
+        }).collect();
+
+        for node in nodes {
+            if is_inside_clip_region(&clip_path, &node) {
+                node.remove_attribute(AId::ClipPath);
+                *is_any_removed = true;
+            }
+        }
+    }
+}
+
+// Checks that one of the 'clipPath' rectangles contains the element including its stroke.
+fn is_inside_clip_region(clip_path: &Node, node: &Node) -> bool {
+    // the text bbox is only an approximation
+    if super::rm_offscreen_elements::has_text(node) {
+        return false;
+    }
+
+    let bbox = match geom::calc_bbox(node, true) {
+        Some(Some(r)) => r,
+        _ => return false,
+    };
+
+    let mut ts = match clip_path.attribute_value(AId::Transform) {
+        Some(AttributeValue::Transform(ts)) => ts,
+        Some(_) => return false,
+        None => Transform::default(),
+    };
+
+    if clip_path.has_attribute_with_value(AId::ClipPathUnits, ValueId::ObjectBoundingBox) {
+        let r = match geom::calc_bbox(node, false) {
+            Some(Some(r)) => r,
+            _ => return false,
+        };
+
+        let mut bbox_ts = Transform::new(r.width(), 0.0, 0.0, r.height(), r.x1, r.y1);
+        bbox_ts.append(&ts);
+        ts = bbox_ts;
+    }
+
+    clip_path.children().svg().any(|child| {
+        if !child.is_tag_name(EId::Rect) || child.has_attribute(AId::ClipPath) {
+            return false;
+        }
+
+        if child.has_attribute_with_value(AId::Display, ValueId::None) {
+            return false;
+        }
+
+        match geom::resolve_inherited(&child, AId::Visibility) {
+            Some(AttributeValue::PredefValue(ValueId::Visible)) | None => {}
+            _ => return false,
+        }
+
+        {
+            let attrs = child.attributes();
+            for aid in &[AId::Rx, AId::Ry] {
+                if get_value!(attrs, Length, *aid, Length::zero()).num.fuzzy_ne(&0.0) {
+                    return false;
+                }
+            }
+        }
+
+        let mut child_ts = ts;
+        match child.attribute_value(AId::Transform) {
+            Some(AttributeValue::Transform(t)) => child_ts.append(&t),
+            Some(_) => return false,
+            None => {}
+        }
+
+        // a rotated rectangle is not a rectangle anymore
+        let is_aligned =    (child_ts.b.abs() < 1e-9 && child_ts.c.abs() < 1e-9)
+                         || (child_ts.a.abs() < 1e-9 && child_ts.d.abs() < 1e-9);
+        if !is_aligned {
+            return false;
+        }
+
+        match geom::calc_shape_bbox(&child) {
+            Some(r) => r.transform(&child_ts).contains(&bbox),
+            None => false,
+        }
+    })
+}
+
+// Checks that the mask contains a single opaque white shape, which means that it works
+// like a clip path, and that the masked elements are not rendered outside their bbox.
+fn is_simple_mask(mask: &Node) -> bool {
+    if    mask.has_attribute_with_value(AId::MaskUnits, ValueId::UserSpaceOnUse)
+       || !is_default_mask_region(mask) {
+        return false;
+    }
+
+    if mask.children().count() != 1 {
+        return false;
+    }
+
+    let child = mask.first_child().unwrap();
+    if !is_simple_shape(&child) || child.is_used() {
+        return false;
+    }
+
+    let white = Color { red: 255, green: 255, blue: 255 };
+    match child.attribute_value(AId::Fill) {
+        Some(AttributeValue::Color(c)) if c == white => {}
+        _ => return false,
+    }
+
+    if    get_number(&child, AId::Opacity).fuzzy_ne(&1.0)
+       || get_number(mask, AId::Opacity).fuzzy_ne(&1.0) {
+        return false;
+    }
+
+    match geom::resolve_inherited(&child, AId::FillOpacity) {
+        Some(AttributeValue::Number(n)) if n.fuzzy_ne(&1.0) => return false,
+        _ => {}
+    }
+
+    if child.has_attributes(&[AId::Visibility, AId::Display, AId::ClipPath]) {
+        return false;
+    }
+
+    mask.linked_nodes().all(|n| is_simple_shape(&n))
+}
+
+// Checks that the shape is rendered only inside its bbox.
+fn is_simple_shape(node: &Node) -> bool {
+    if !(node.is_basic_shape() || node.is_tag_name(EId::Path)) {
+        return false;
+    }
+
+    if node.has_attributes(&[AId::Filter, AId::Mask]) {
+        return false;
+    }
+
+    // markers are inheritable
+    for aid in &[AId::Marker, AId::MarkerStart, AId::MarkerMid, AId::MarkerEnd] {
+        match geom::resolve_inherited(node, *aid) {
+            Some(AttributeValue::PredefValue(ValueId::None)) | None => {}
+            _ => return false,
+        }
+    }
+
+    geom::resolve_stroke_width(node) == Some(0.0)
+}
+
+fn is_default_mask_region(mask: &Node) -> bool {
+    let attrs = mask.attributes();
+    for aid in &[AId::X, AId::Y, AId::Width, AId::Height] {
+        let def = if *aid == AId::X || *aid == AId::Y { -0.1 } else { 1.2 };
+        match attrs.get_value(*aid) {
+            Some(&AttributeValue::Length(len)) => {
+                let n = match len.unit {
+                    Unit::None => len.num,
+                    Unit::Percent => len.num / 100.0,
+                    _ => return false,
+                };
+
+                if n.fuzzy_ne(&def) {
+                    return false;
+                }
+            }
+            None => {}
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+// Checks that the mask content covers the masked elements entirely.
+fn is_covering_mask(mask: &Node, child: &Node) -> bool {
+    if !child.is_tag_name(EId::Rect) || child.has_attribute(AId::Transform) {
+        return false;
+    }
+
+    let is_bbox = mask.has_attribute_with_value(AId::MaskContentUnits, ValueId::ObjectBoundingBox);
+
+    let rect = {
+        let attrs = child.attributes();
+
+        // rounded corners don't cover the bbox
+        for aid in &[AId::Rx, AId::Ry] {
+            if get_value!(attrs, Length, *aid, Length::zero()).num.fuzzy_ne(&0.0) {
+                return false;
+            }
+        }
+
+        let mut values = [0.0; 4];
+        for (i, aid) in [AId::X, AId::Y, AId::Width, AId::Height].iter().enumerate() {
+            let len = get_value!(attrs, Length, *aid, Length::zero());
+            values[i] = match len.unit {
+                Unit::None => len.num,
+                Unit::Percent if is_bbox => len.num / 100.0,
+                _ => return false,
+            };
+        }
+
+        Rect::new(values[0], values[1], values[2], values[3])
+    };
+
+    mask.linked_nodes().all(|n| {
+        let bbox = if is_bbox {
+            Rect::new(0.0, 0.0, 1.0, 1.0)
+        } else {
+            match geom::calc_bbox(&n, false) {
+                Some(Some(r)) => r,
+                _ => return false,
+            }
+        };
+
+        rect.contains(&bbox)
+    })
+}
+
+fn conv_mask_to_clip_path(mask: &Node, child: &Node) {
+    if mask.linked_nodes().any(|n| n.has_attribute(AId::ClipPath)) {
+        return;
+    }
+
+    let is_bbox = mask.has_attribute_with_value(AId::MaskContentUnits, ValueId::ObjectBoundingBox);
+
+    mask.set_tag_name(EId::ClipPath);
+    mask.remove_attributes(&[AId::MaskUnits, AId::MaskContentUnits,
+                             AId::X, AId::Y, AId::Width, AId::Height]);
+    if is_bbox {
+        mask.set_attribute(AId::ClipPathUnits, ValueId::ObjectBoundingBox);
+    }
+
+    // 'fill-rule' is not used inside the 'clipPath', so we should replace it with 'clip-rule'
+    let fill_rule = geom::resolve_inherited(child, AId::FillRule)
+                        .unwrap_or(AttributeValue::PredefValue(ValueId::Nonzero));
+    let clip_rule = geom::resolve_inherited(child, AId::ClipRule)
+                        .unwrap_or(AttributeValue::PredefValue(ValueId::Nonzero));
+    if fill_rule != clip_rule {
+        child.set_attribute(AId::ClipRule, fill_rule);
+    }
+    child.remove_attributes(&[AId::Fill, AId::FillOpacity, AId::FillRule, AId::Opacity]);
+
+    for link in mask.linked_nodes().collect::<Vec<Node>>() {
+        link.remove_attribute(AId::Mask);
+        // if it's fail - it's already a huge problem, so unwrap is harmless
+        link.set_link_attribute(AId::ClipPath, mask.clone()).unwrap();
+    }
+}
+
+fn get_number(node: &Node, aid: AId) -> f64 {
+    match node.attribute_value(aid) {
+        Some(AttributeValue::Number(n)) => n,
+        _ => 1.0,
+    }
+}
+
 // Paths with empty 'd' attribute are invisible and we can remove them.
 fn process_paths(doc: &Document, is_any_removed: &mut bool) {
     fn is_invisible(node: &Node) -> bool {
@@ -162,6 +482,194 @@ fn _process_display_attribute(parent: &Node, nodes: &mut Vec<Node>, is_any_remov
     }
 }
 
+// Remove elements with 'visibility:hidden'.
+fn process_visibility(doc: &Document, is_any_removed: &mut bool) {
+    let mut nodes = Vec::with_capacity(16);
+
+    _process_visibility(&doc.root(), false, &mut nodes);
+
+    if !nodes.is_empty() {
+        *is_any_removed = true;
+    }
+
+    for n in nodes {
+        n.remove();
+    }
+}
+
+fn _process_visibility(parent: &Node, is_parent_hidden: bool, nodes: &mut Vec<Node>) {
+    for node in parent.children().svg() {
+        let is_hidden = match node.attribute_value(AId::Visibility) {
+            Some(AttributeValue::PredefValue(ValueId::Visible)) => false,
+              Some(AttributeValue::PredefValue(ValueId::Hidden))
+            | Some(AttributeValue::PredefValue(ValueId::Collapse)) => true,
+            _ => is_parent_hidden,
+        };
+
+        // 'visibility' is inheritable, but children can be visible again,
+        // so we are checking only elements without children
+        let is_leaf = match node.tag_id().unwrap() {
+            EId::Path | EId::Image => true,
+            EId::Text => !node.descendants().svg().skip(1).any(|n| n.has_attribute(AId::Visibility)),
+            EId::Use => {
+                // the referenced element inherits 'visibility' from 'use'
+                match node.attribute_value(AId::XlinkHref) {
+                    Some(AttributeValue::Link(link)) => {
+                        !link.descendants().svg().any(|n| n.has_attribute(AId::Visibility))
+                    }
+                    _ => true,
+                }
+            }
+            _ => node.is_basic_shape(),
+        };
+
+        if is_leaf {
+            // 'painted', 'fill', 'stroke' and 'all' values of the 'pointer-events'
+            // attribute are working even on hidden elements
+            if is_hidden && can_remove_invisible(&node) {
+                nodes.push(node.clone());
+            }
+        } else if node.has_children() {
+            _process_visibility(&node, is_hidden, nodes);
+        }
+    }
+}
+
+// Remove elements with 'opacity:0'.
+fn process_opacity(doc: &Document, is_any_removed: &mut bool) {
+    let mut nodes = Vec::with_capacity(16);
+
+    collect_nodes(&doc.root(), &|node| {
+           is_graphic_element(node)
+        && get_number(node, AId::Opacity).fuzzy_eq(&0.0)
+        // the 'clipPath' children are used only as a geometry
+        && !node.parents().any(|n| n.is_tag_name(EId::ClipPath))
+        && can_remove_invisible(node)
+    }, &mut nodes);
+
+    if !nodes.is_empty() {
+        *is_any_removed = true;
+    }
+
+    for n in nodes {
+        n.remove();
+    }
+}
+
+// Remove elements with non-invertible transform, like 'matrix(0 0 0 0 0 0)'.
+//
+// 'If the transformation matrix is not invertible, then the element is not rendered.'
+fn process_invalid_transform(doc: &Document, is_any_removed: &mut bool) {
+    let mut nodes = Vec::with_capacity(16);
+
+    collect_nodes(&doc.root(), &|node| {
+        if !is_graphic_element(node) {
+            return false;
+        }
+
+        match node.attribute_value(AId::Transform) {
+            Some(AttributeValue::Transform(ts)) => {
+                (ts.a * ts.d - ts.b * ts.c).fuzzy_eq(&0.0) && can_remove_invisible(node)
+            }
+            _ => false,
+        }
+    }, &mut nodes);
+
+    if !nodes.is_empty() {
+        *is_any_removed = true;
+    }
+
+    for n in nodes {
+        n.remove();
+    }
+}
+
+// Remove shapes without fill, stroke and markers.
+fn process_unpainted(doc: &Document, is_any_removed: &mut bool) {
+    fn is_unpainted(node: &Node) -> bool {
+        if !(node.is_basic_shape() || node.is_tag_name(EId::Path)) {
+            return false;
+        }
+
+        // the 'clipPath' children are used only as a geometry
+        if node.parents().any(|n| n.is_tag_name(EId::ClipPath)) {
+            return false;
+        }
+
+        // filter can render something even without the source graphic
+        if node.has_attribute(AId::Filter) {
+            return false;
+        }
+
+        match geom::resolve_inherited(node, AId::Fill) {
+            Some(AttributeValue::PredefValue(ValueId::None)) => {}
+            _ => return false,
+        }
+
+        if geom::resolve_stroke_width(node) != Some(0.0) {
+            return false;
+        }
+
+        for aid in &[AId::Marker, AId::MarkerStart, AId::MarkerMid, AId::MarkerEnd] {
+            match geom::resolve_inherited(node, *aid) {
+                Some(AttributeValue::PredefValue(ValueId::None)) | None => {}
+                _ => return false,
+            }
+        }
+
+        can_remove_invisible(node)
+    }
+
+    let mut nodes = Vec::with_capacity(16);
+    collect_nodes(&doc.root(), &is_unpainted, &mut nodes);
+
+    if !nodes.is_empty() {
+        *is_any_removed = true;
+    }
+
+    for n in nodes {
+        n.remove();
+    }
+}
+
+// Collects elements that match the predicate, but not their descendants.
+fn collect_nodes<F>(parent: &Node, pred: &F, nodes: &mut Vec<Node>)
+    where F: Fn(&Node) -> bool
+{
+    for node in parent.children().svg() {
+        if pred(&node) {
+            nodes.push(node.clone());
+        } else if node.has_children() {
+            collect_nodes(&node, pred, nodes);
+        }
+    }
+}
+
+fn is_graphic_element(node: &Node) -> bool {
+    match node.tag_id().unwrap() {
+        EId::G | EId::Path | EId::Text | EId::Image | EId::Use => true,
+        _ => node.is_basic_shape(),
+    }
+}
+
+// Checks that the invisible element can be removed without side effects.
+fn can_remove_invisible(node: &Node) -> bool {
+    // element and its children must be unused
+    if node.descendants().svg().any(|n| n.is_used()) {
+        return false;
+    }
+
+    // invisible elements can still receive pointer events
+    match geom::resolve_inherited(node, AId::PointerEvents) {
+        Some(AttributeValue::PredefValue(ValueId::None)) | None => {}
+        _ => return false,
+    }
+
+    // Invisible elements are still affecting the parent bbox, which is used by
+    // filters, masks and clip paths with the 'objectBoundingBox' units.
+    !node.parents().any(|n| n.has_attributes(&[AId::Filter, AId::Mask, AId::ClipPath]))
+}
+
 // remove 'filter' elements without children
 fn process_empty_filter(doc: &Document, is_any_removed: &mut bool) {
     let nodes: Vec<Node> = doc.descendants().svg()
@@ -245,21 +753,28 @@ fn process_gradients(doc: &Document, is_any_removed: &mut bool) {
         }
     }
 
-    // TODO: If 'x1' = 'x2' and 'y1' = 'y2', then the area to be painted will be painted as
-    //       a single color using the color and opacity of the last gradient stop.
-
     {
-        // 'If one stop is defined, then paint with the solid color fill using the color
-        // defined for that gradient stop.'
         let iter = doc.descendants().svg()
                       .filter(|n| super::is_gradient(n))
-                      .filter(|n| n.children().count() == 1 && !n.has_attribute(AId::XlinkHref));
+                      .filter(|n| n.has_children() && !n.has_attribute(AId::XlinkHref));
 
         for n in iter {
-            let stop = n.children().nth(0).unwrap();
-            // unwrap is safe, because we already resolved all 'stop' attributes
-            let color = *stop.attribute_value(AId::StopColor).unwrap().as_color().unwrap();
-            let opacity = *stop.attribute_value(AId::StopOpacity).unwrap().as_number().unwrap();
+            let (color, opacity) = match get_solid_color(&n) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            // gradients that are linked to the current one will lose their stops
+            if n.linked_nodes().any(|l| super::is_gradient(&l) && l.is_used()) {
+                continue;
+            }
+
+            // Paint opacity set on a container can be overridden by its children,
+            // so the stop opacity can be applied only to leaf elements.
+            if    opacity.fuzzy_ne(&1.0)
+               && n.linked_nodes().any(|l| !super::is_gradient(&l) && l.has_children()) {
+                continue;
+            }
 
             // replace links with colors, but not in gradients,
             // because it will lead to 'xlink:href=#ffffff', which is wrong
@@ -268,12 +783,10 @@ fn process_gradients(doc: &Document, is_any_removed: &mut bool) {
                          .collect::<Vec<Node>>() {
                 while let Some(aid) = find_link_attribute(&link, &n) {
                     link.set_attribute(aid, color);
-                    if opacity.fuzzy_ne(&1.0) {
-                        match aid {
-                            AId::Fill => link.set_attribute(AId::FillOpacity, opacity),
-                            AId::Stroke => link.set_attribute(AId::StrokeOpacity, opacity),
-                            _ => {}
-                        }
+                    match aid {
+                        AId::Fill => set_paint_opacity(&link, AId::FillOpacity, opacity),
+                        AId::Stroke => set_paint_opacity(&link, AId::StrokeOpacity, opacity),
+                        _ => {}
                     }
                 }
             }
@@ -290,6 +803,84 @@ fn process_gradients(doc: &Document, is_any_removed: &mut bool) {
     }
 }
 
+// Checks that the gradient will be rendered as a solid color.
+//
+// Returns the color and the opacity of this color.
+fn get_solid_color(node: &Node) -> Option<(Color, f64)> {
+    let stops: Vec<(Color, f64)> = try_opt!(node.children()
+                                                .map(|n| get_stop_color(&n))
+                                                .collect());
+
+    // 'If one stop is defined, then paint with the solid color fill using the color
+    // defined for that gradient stop.'
+    //
+    // Or all stops have the same color.
+    let first = stops[0];
+    if stops.iter().all(|&(c, o)| c == first.0 && o.fuzzy_eq(&first.1)) {
+        return Some(first);
+    }
+
+    // 'If x1 = x2 and y1 = y2, then the area to be painted will be painted as a single color
+    // using the color and opacity of the last gradient stop.'
+    //
+    // The same rule is applied to a 'radialGradient' with 'r' = 0.
+    let is_degenerate = {
+        let attrs = node.attributes();
+        if node.is_tag_name(EId::LinearGradient) {
+            let x1 = get_value!(attrs, Length, AId::X1, Length::zero());
+            let y1 = get_value!(attrs, Length, AId::Y1, Length::zero());
+            let x2 = get_value!(attrs, Length, AId::X2, Length::new(100.0, Unit::Percent));
+            let y2 = get_value!(attrs, Length, AId::Y2, Length::zero());
+            is_equal_len(x1, x2) && is_equal_len(y1, y2)
+        } else {
+            let r = get_value!(attrs, Length, AId::R, Length::new(50.0, Unit::Percent));
+            r.num.fuzzy_eq(&0.0)
+        }
+    };
+
+    if is_degenerate {
+        // unwrap is safe, because the gradient has children
+        return Some(*stops.last().unwrap());
+    }
+
+    None
+}
+
+fn is_equal_len(l1: Length, l2: Length) -> bool {
+    l1.unit == l2.unit && l1.num.fuzzy_eq(&l2.num)
+}
+
+// Returns `None` if the stop color is not a plain color, like 'currentColor'.
+fn get_stop_color(node: &Node) -> Option<(Color, f64)> {
+    let attrs = node.attributes();
+
+    let color = match attrs.get_value(AId::StopColor) {
+        Some(&AttributeValue::Color(c)) => c,
+        Some(_) => return None,
+        None => Color { red: 0, green: 0, blue: 0 },
+    };
+
+    let opacity = get_value!(attrs, Number, AId::StopOpacity, 1.0);
+    Some((color, opacity))
+}
+
+// Multiplies paint opacity by the gradient stop opacity.
+fn set_paint_opacity(node: &Node, aid: AId, opacity: f64) {
+    // 'fill-opacity' and 'stroke-opacity' are inheritable
+    let curr = if let Some(AttributeValue::Number(n)) = node.attribute_value(aid) {
+        n
+    } else if let Some(p) = node.parents().find(|n| n.has_attribute(aid)) {
+        *p.attribute_value(aid).unwrap().as_number().unwrap_or(&1.0)
+    } else {
+        1.0
+    };
+
+    let opacity = curr * opacity;
+    if opacity.fuzzy_ne(&curr) || node.has_attribute(aid) {
+        node.set_attribute(aid, opacity);
+    }
+}
+
 fn find_link_attribute(node: &Node, link: &Node) -> Option<AId> {
     let attrs = node.attributes();
 
@@ -307,6 +898,24 @@ fn find_link_attribute(node: &Node, link: &Node) -> Option<AId> {
     None
 }
 
+// 'polyline' and 'polygon' elements without points are invisible
+fn process_polylines(doc: &Document, is_any_removed: &mut bool) {
+    fn is_invisible(node: &Node) -> bool {
+        if !(node.is_tag_name(EId::Polyline) || node.is_tag_name(EId::Polygon)) {
+            return false;
+        }
+
+        match node.attribute_value(AId::Points) {
+            Some(AttributeValue::NumberList(ref points)) => points.is_empty(),
+            Some(_) => false,
+            None => true,
+        }
+    }
+
+    let c = doc.drain(is_invisible);
+    if c != 0 { *is_any_removed = true; }
+}
+
 // remove rect's with zero size
 fn process_rect(doc: &Document, is_any_removed: &mut bool) {
     fn is_invisible(node: &Node) -> bool {
@@ -329,6 +938,7 @@ mod tests {
     use super::*;
     use svgdom::{Document, WriteToString};
     use task::{group_defs, remove_empty_defs};
+    use options::Options;
 
     macro_rules! test {
         ($name:ident, $in_text:expr, $out_text:expr) => (
@@ -338,7 +948,11 @@ mod tests {
                 // we must prepare defs, because `remove_invisible_elements`
                 // invokes `remove_unused_defs`
                 group_defs(&doc);
-                remove_invisible_elements(&doc);
+                let mut options = Options::default();
+                options.remove_hidden_elements = true;
+                options.remove_unpainted_elements = true;
+                options.remove_empty_shapes = true;
+                remove_invisible_elements(&doc, &options);
                 // removes `defs` element
                 remove_empty_defs(&doc);
                 assert_eq_text!(doc.to_string_with_opt(&write_opt_for_tests!()), $out_text);
@@ -418,6 +1032,66 @@ b"<svg>
     </clipPath>
 </svg>",
 "<svg/>
+");
+
+    test!(rm_unclipped_1,
+b"<svg>
+    <clipPath id='cp1' transform='translate(-10 -10)'>
+        <rect height='100' width='100'/>
+    </clipPath>
+    <rect clip-path='url(#cp1)' height='5' stroke='#000000' width='5' x='10' y='10'/>
+    <rect clip-path='url(#cp1)' height='5' transform='translate(50 50)' width='5' x='80' y='80'/>
+    <rect clip-path='url(#cp1)' height='5' width='10' x='85'/>
+</svg>",
+"<svg>
+    <defs>
+        <clipPath id='cp1' transform='translate(-10 -10)'>
+            <rect height='100' width='100'/>
+        </clipPath>
+    </defs>
+    <rect height='5' stroke='#000000' width='5' x='10' y='10'/>
+    <rect height='5' transform='translate(50 50)' width='5' x='80' y='80'/>
+    <rect clip-path='url(#cp1)' height='5' width='10' x='85'/>
+</svg>
+");
+
+    test!(rm_unclipped_2,
+b"<svg>
+    <clipPath id='cp1' clipPathUnits='objectBoundingBox'>
+        <rect height='1.2' width='1.2' x='-0.1' y='-0.1'/>
+    </clipPath>
+    <g clip-path='url(#cp1)'>
+        <circle cx='50' cy='50' r='10' stroke='#000000'/>
+    </g>
+</svg>",
+"<svg>
+    <g>
+        <circle cx='50' cy='50' r='10' stroke='#000000'/>
+    </g>
+</svg>
+");
+
+    // the stroke is outside the clip region
+    test_eq!(keep_unclipped_1,
+b"<svg>
+    <defs>
+        <clipPath id='cp1' clipPathUnits='objectBoundingBox'>
+            <rect height='1' width='1'/>
+        </clipPath>
+    </defs>
+    <rect clip-path='url(#cp1)' height='5' stroke='#000000' width='5'/>
+</svg>
+");
+
+    test_eq!(keep_unclipped_2,
+b"<svg>
+    <defs>
+        <clipPath id='cp1'>
+            <rect height='100' transform='rotate(10)' width='100'/>
+        </clipPath>
+    </defs>
+    <rect clip-path='url(#cp1)' height='5' width='5' x='10' y='10'/>
+</svg>
 ");
 
     test!(rm_path_1,
@@ -524,6 +1198,279 @@ b"<svg>
 "<svg>
     <rect fill='#ff0000' fill-opacity='0.5' height='5' stroke='#ff0000' stroke-opacity='0.5' width='5'/>
 </svg>
+");
+
+    test!(rm_gradient_4,
+b"<svg>
+    <linearGradient id='lg1' x1='5' y1='5' x2='5' y2='5'>
+        <stop offset='0' stop-color='#00ff00'/>
+        <stop offset='1' stop-color='#ff0000' stop-opacity='0.5'/>
+    </linearGradient>
+    <rect fill='url(#lg1)' height='5' width='5'/>
+</svg>",
+"<svg>
+    <rect fill='#ff0000' fill-opacity='0.5' height='5' width='5'/>
+</svg>
+");
+
+    test!(rm_gradient_5,
+b"<svg>
+    <radialGradient id='rg1' r='0'>
+        <stop offset='0' stop-color='#00ff00'/>
+        <stop offset='1' stop-color='#ff0000'/>
+    </radialGradient>
+    <rect fill='url(#rg1)' height='5' width='5'/>
+</svg>",
+"<svg>
+    <rect fill='#ff0000' height='5' width='5'/>
+</svg>
+");
+
+    test!(rm_gradient_6,
+b"<svg>
+    <linearGradient id='lg1'>
+        <stop offset='0' stop-color='#ff0000' stop-opacity='0.5'/>
+        <stop offset='1' stop-color='#ff0000' stop-opacity='0.5'/>
+    </linearGradient>
+    <g fill-opacity='0.5'>
+        <rect fill='url(#lg1)' height='5' width='5'/>
+    </g>
+</svg>",
+"<svg>
+    <g fill-opacity='0.5'>
+        <rect fill='#ff0000' fill-opacity='0.25' height='5' width='5'/>
+    </g>
+</svg>
+");
+
+    test_eq!(keep_gradient_1,
+b"<svg>
+    <linearGradient id='lg1' x1='0' x2='5' y1='0' y2='0'>
+        <stop offset='0' stop-color='#00ff00'/>
+        <stop offset='1' stop-color='#ff0000'/>
+    </linearGradient>
+    <rect fill='url(#lg1)' height='5' width='5'/>
+</svg>
+");
+
+    test_eq!(keep_gradient_2,
+b"<svg>
+    <linearGradient id='lg1' x1='5' x2='5' y1='5' y2='5'>
+        <stop offset='0' stop-color='#00ff00'/>
+        <stop offset='1' stop-color='#ff0000'/>
+    </linearGradient>
+    <linearGradient id='lg2' x1='0' x2='5' xlink:href='#lg1'/>
+    <rect fill='url(#lg1)' height='5' width='5'/>
+    <rect fill='url(#lg2)' height='5' width='5'/>
+</svg>
+");
+
+    test_eq!(keep_gradient_3,
+b"<svg>
+    <linearGradient id='lg1'>
+        <stop offset='0.5' stop-color='#ff0000' stop-opacity='0.5'/>
+    </linearGradient>
+    <g fill='url(#lg1)'>
+        <rect fill-opacity='0.5' height='5' width='5'/>
+    </g>
+</svg>
+");
+
+    #[test]
+    fn keep_gradient_4() {
+        let doc = Document::from_data(b"<svg>
+    <linearGradient>
+        <stop offset='0'/>
+    </linearGradient>
+</svg>").unwrap();
+        let stop = doc.descendants().svg().find(|n| n.is_tag_name(EId::Stop)).unwrap();
+        assert!(get_stop_color(&stop).is_some());
+
+        // gradient with a 'currentColor' stop can't be replaced with a solid color
+        stop.set_attribute(AId::StopColor, ValueId::CurrentColor);
+        assert!(get_stop_color(&stop).is_none());
+        assert!(get_solid_color(&stop.parent().unwrap()).is_none());
+    }
+
+    test!(rm_mask_1,
+b"<svg>
+    <mask id='m1'/>
+    <rect height='5' mask='url(#m1)' width='5'/>
+    <rect height='5' width='5'/>
+</svg>",
+"<svg>
+    <rect height='5' width='5'/>
+</svg>
+");
+
+    test!(rm_mask_2,
+b"<svg>
+    <mask id='m1' maskContentUnits='objectBoundingBox'>
+        <rect fill='#ffffff' height='1' width='1'/>
+    </mask>
+    <rect height='5' mask='url(#m1)' width='5'/>
+</svg>",
+"<svg>
+    <rect height='5' width='5'/>
+</svg>
+");
+
+    test!(rm_mask_3,
+b"<svg>
+    <mask id='m1'>
+        <rect fill='#ffffff' height='20' width='20' x='-5' y='-5'/>
+    </mask>
+    <rect height='5' mask='url(#m1)' width='5' x='5' y='5'/>
+</svg>",
+"<svg>
+    <rect height='5' width='5' x='5' y='5'/>
+</svg>
+");
+
+    test!(conv_mask_1,
+b"<svg>
+    <mask id='m1'>
+        <circle cx='5' cy='5' fill='#ffffff' r='3'/>
+    </mask>
+    <rect height='10' mask='url(#m1)' width='10'/>
+</svg>",
+"<svg>
+    <defs>
+        <clipPath id='m1'>
+            <circle cx='5' cy='5' r='3'/>
+        </clipPath>
+    </defs>
+    <rect clip-path='url(#m1)' height='10' width='10'/>
+</svg>
+");
+
+    test!(conv_mask_2,
+b"<svg>
+    <mask id='m1' maskContentUnits='objectBoundingBox'>
+        <path d='M 0 0 L 1 0 L 1 1 Z' fill='#ffffff' fill-rule='evenodd'/>
+    </mask>
+    <rect height='10' mask='url(#m1)' width='10'/>
+</svg>",
+"<svg>
+    <defs>
+        <clipPath id='m1' clipPathUnits='objectBoundingBox'>
+            <path clip-rule='evenodd' d='M 0 0 L 1 0 L 1 1 Z'/>
+        </clipPath>
+    </defs>
+    <rect clip-path='url(#m1)' height='10' width='10'/>
+</svg>
+");
+
+    test_eq!(keep_mask_1,
+b"<svg>
+    <defs>
+        <mask id='m1'>
+            <circle cx='5' cy='5' fill='#ffffff' r='3'/>
+        </mask>
+    </defs>
+    <rect height='10' mask='url(#m1)' stroke='#000000' width='10'/>
+</svg>
+");
+
+    test_eq!(keep_mask_2,
+b"<svg>
+    <defs>
+        <mask id='m1'>
+            <circle cx='5' cy='5' fill='#ff0000' r='3'/>
+        </mask>
+    </defs>
+    <rect height='10' mask='url(#m1)' width='10'/>
+</svg>
+");
+
+    test!(rm_visibility_1,
+b"<svg>
+    <g visibility='hidden'>
+        <rect height='5' width='5'/>
+        <rect height='5' visibility='visible' width='5'/>
+    </g>
+    <rect height='5' visibility='hidden' width='5'/>
+</svg>",
+"<svg>
+    <g visibility='hidden'>
+        <rect height='5' visibility='visible' width='5'/>
+    </g>
+</svg>
+");
+
+    test_eq!(keep_visibility_1,
+b"<svg>
+    <defs>
+        <rect id='rect1' height='5' visibility='hidden' width='5'/>
+    </defs>
+    <use xlink:href='#rect1'/>
+    <rect height='5' pointer-events='all' visibility='hidden' width='5'/>
+</svg>
+");
+
+    test!(rm_opacity_1,
+b"<svg>
+    <g opacity='0'>
+        <rect height='5' width='5'/>
+    </g>
+    <rect height='5' opacity='0' width='5'/>
+    <rect height='5' width='5'/>
+</svg>",
+"<svg>
+    <rect height='5' width='5'/>
+</svg>
+");
+
+    test_eq!(keep_opacity_1,
+b"<svg>
+    <defs>
+        <clipPath id='cp1'>
+            <rect height='4' opacity='0' width='4'/>
+        </clipPath>
+    </defs>
+    <rect clip-path='url(#cp1)' height='5' width='5'/>
+    <g clip-path='url(#cp1)'>
+        <rect height='5' opacity='0' width='5'/>
+    </g>
+</svg>
+");
+
+    test!(rm_invalid_transform_1,
+b"<svg>
+    <rect height='5' transform='matrix(0 0 0 0 0 0)' width='5'/>
+    <g transform='scale(0)'>
+        <rect height='5' width='5'/>
+    </g>
+</svg>",
+"<svg/>
+");
+
+    test!(rm_unpainted_1,
+b"<svg>
+    <rect fill='none' height='5' width='5'/>
+    <rect fill='none' height='5' stroke='#000000' stroke-width='0' width='5'/>
+    <g fill='none'>
+        <path d='M 10 20 L 30 40'/>
+    </g>
+</svg>",
+"<svg>
+    <g fill='none'/>
+</svg>
+");
+
+    test_eq!(keep_unpainted_1,
+b"<svg>
+    <rect fill='none' height='5' stroke='#000000' width='5'/>
+    <rect height='5' stroke-width='0' width='5'/>
+</svg>
+");
+
+    test!(rm_polyline_1,
+b"<svg>
+    <polyline/>
+    <polygon/>
+</svg>",
+"<svg/>
 ");
 
     test!(rm_rect_1,
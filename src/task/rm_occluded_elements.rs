@@ -0,0 +1,429 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+use std::f64::consts::PI;
+
+use super::short::{EId, AId};
+use super::rm_offscreen_elements::has_text;
+
+use geom::{self, Rect};
+
+use svgdom::{Document, Node, AttributeValue, ValueId};
+use svgdom::types::{Length, FuzzyEq};
+
+// Removes elements which are completely covered by subsequent opaque shapes.
+pub fn remove_occluded_elements(doc: &Document) {
+    // doc must contain 'svg' node, so we can safely unwrap
+    let svg = doc.svg_element().unwrap();
+
+    // rendered elements in the painting order
+    let mut elems = Vec::new();
+    collect_elements(&svg, &mut elems);
+
+    let occluders: Vec<(usize, &Node, Shape)> = elems.iter().enumerate()
+        .filter_map(|(i, &(ref n, _))| to_occluder(n).map(|s| (i, n, s)))
+        .collect();
+
+    if occluders.is_empty() {
+        return;
+    }
+
+    let mut nodes = Vec::new();
+    let mut i = 0;
+    while i < elems.len() {
+        let (ref node, end) = elems[i];
+
+        // only elements rendered after the current one and its children can cover it
+        let is_occluded = can_remove(node) && match geom::calc_canvas_bbox(node, true) {
+            Some(Some(bbox)) => {
+                occluders.iter()
+                    .filter(|&&(idx, _, _)| idx >= end)
+                    .any(|&(_, c, ref shape)| shape.contains(&bbox) && is_covering(c, node))
+            }
+            _ => false,
+        };
+
+        if is_occluded {
+            nodes.push(node.clone());
+            // skip children
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+
+    for n in nodes {
+        n.remove();
+    }
+}
+
+enum Shape {
+    Rect(Rect),
+    // a convex polygon with a positive orientation in the canvas coordinates
+    Polygon(Vec<(f64, f64)>),
+}
+
+impl Shape {
+    fn contains(&self, r: &Rect) -> bool {
+        match *self {
+            Shape::Rect(ref rect) => rect.contains(r),
+            Shape::Polygon(ref points) => {
+                let corners = [(r.x1, r.y1), (r.x2, r.y1), (r.x2, r.y2), (r.x1, r.y2)];
+                corners.iter().all(|p| {
+                    (0..points.len()).all(|i| {
+                        let a = points[i];
+                        let b = points[(i + 1) % points.len()];
+                        (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0) >= 0.0
+                    })
+                })
+            }
+        }
+    }
+}
+
+// Collects elements with the index of the first element after their children.
+fn collect_elements(parent: &Node, elems: &mut Vec<(Node, usize)>) {
+    for node in parent.children().svg() {
+        // unwrap is safe, because we are iterating only over SVG elements
+        match node.tag_id().unwrap() {
+            EId::G | EId::A => {
+                let idx = elems.len();
+                elems.push((node.clone(), 0));
+                collect_elements(&node, elems);
+                elems[idx].1 = elems.len();
+            }
+            // 'switch' children are rendered conditionally, so we are not processing them
+              EId::Switch
+            | EId::Path
+            | EId::Text
+            | EId::Image
+            | EId::Use => {
+                let idx = elems.len();
+                elems.push((node, idx + 1));
+            }
+            _ if node.is_basic_shape() => {
+                let idx = elems.len();
+                elems.push((node, idx + 1));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn can_remove(node: &Node) -> bool {
+    !node.descendants().svg().any(|n| n.is_used()) && !has_text(node)
+}
+
+// Returns the canvas shape of the element if it's painted with an opaque solid color.
+fn to_occluder(node: &Node) -> Option<Shape> {
+    if !is_plain_layer(node) {
+        return None;
+    }
+
+    match geom::resolve_inherited(node, AId::Visibility) {
+        Some(AttributeValue::PredefValue(ValueId::Hidden)) |
+        Some(AttributeValue::PredefValue(ValueId::Collapse)) => return None,
+        _ => {}
+    }
+
+    match geom::resolve_inherited(node, AId::Fill) {
+        Some(AttributeValue::Color(_)) | None => {}
+        _ => return None,
+    }
+
+    match geom::resolve_inherited(node, AId::FillOpacity) {
+        Some(AttributeValue::Number(n)) if n.fuzzy_eq(&1.0) => {}
+        None => {}
+        _ => return None,
+    }
+
+    let ts = try_opt!(geom::calc_canvas_transform(node));
+
+    match node.tag_id().unwrap() {
+        EId::Rect => {
+            let attrs = node.attributes();
+            for aid in &[AId::Rx, AId::Ry] {
+                if get_value!(attrs, Length, *aid, Length::zero()).num != 0.0 {
+                    return None;
+                }
+            }
+
+            // only axis-aligned rectangles are supported
+            let is_aligned =    (ts.b.abs() < EPSILON && ts.c.abs() < EPSILON)
+                             || (ts.a.abs() < EPSILON && ts.d.abs() < EPSILON);
+            if !is_aligned {
+                return None;
+            }
+
+            let r = try_opt!(geom::calc_shape_bbox(node));
+            Some(Shape::Rect(r.transform(&ts)))
+        }
+        EId::Polygon => {
+            let points: Vec<(f64, f64)> = match node.attribute_value(AId::Points) {
+                Some(AttributeValue::NumberList(ref list)) => {
+                    list.chunks(2).filter(|p| p.len() == 2).map(|p| ts.apply(p[0], p[1])).collect()
+                }
+                _ => return None,
+            };
+
+            to_convex_polygon(points).map(Shape::Polygon)
+        }
+        _ => None,
+    }
+}
+
+const EPSILON: f64 = 1e-9;
+
+fn to_convex_polygon(points: Vec<(f64, f64)>) -> Option<Vec<(f64, f64)>> {
+    let mut pts: Vec<(f64, f64)> = Vec::with_capacity(points.len());
+    for p in points {
+        if pts.last() != Some(&p) {
+            pts.push(p);
+        }
+    }
+
+    if pts.len() > 1 && pts.first() == pts.last() {
+        pts.pop();
+    }
+
+    if pts.len() < 3 {
+        return None;
+    }
+
+    let len = pts.len();
+    let mut sign = 0.0;
+    let mut turn = 0.0;
+    for i in 0..len {
+        let a = pts[i];
+        let b = pts[(i + 1) % len];
+        let c = pts[(i + 2) % len];
+
+        let (ux, uy) = (b.0 - a.0, b.1 - a.1);
+        let (vx, vy) = (c.0 - b.0, c.1 - b.1);
+        let cross = ux * vy - uy * vx;
+
+        if cross != 0.0 {
+            if sign == 0.0 {
+                sign = cross.signum();
+            } else if sign * cross < 0.0 {
+                return None;
+            }
+        }
+
+        turn += cross.atan2(ux * vx + uy * vy);
+    }
+
+    // a simple convex polygon turns exactly once, unlike a pentagram
+    if sign == 0.0 || (turn.abs() - 2.0 * PI).abs() > EPSILON {
+        return None;
+    }
+
+    if sign < 0.0 {
+        pts.reverse();
+    }
+
+    Some(pts)
+}
+
+// Checks that nothing between the occluder and the covered element
+// changes how they are composited.
+fn is_covering(occluder: &Node, node: &Node) -> bool {
+    let node_parents: Vec<Node> = node.parents().collect();
+    for p in occluder.parents() {
+        if node_parents.contains(&p) {
+            break;
+        }
+
+        if !is_plain_layer(&p) {
+            return false;
+        }
+    }
+
+    // filters can move the covered element
+    let occluder_parents: Vec<Node> = occluder.parents().collect();
+    for p in node.parents() {
+        if occluder_parents.contains(&p) {
+            break;
+        }
+
+        if p.has_attribute(AId::Filter) {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn is_plain_layer(node: &Node) -> bool {
+    if node.has_attributes(&[AId::Filter, AId::Mask, AId::ClipPath]) {
+        return false;
+    }
+
+    if node.has_attribute_with_value(AId::Display, ValueId::None) {
+        return false;
+    }
+
+    match node.attribute_value(AId::Opacity) {
+        Some(AttributeValue::Number(n)) => n.fuzzy_eq(&1.0),
+        Some(_) => false,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::{Document, WriteToString};
+
+    macro_rules! test {
+        ($name:ident, $in_text:expr, $out_text:expr) => (
+            base_test!($name, remove_occluded_elements, $in_text, $out_text);
+        )
+    }
+
+    macro_rules! test_eq {
+        ($name:ident, $in_text:expr) => (
+            test!($name, $in_text, String::from_utf8_lossy($in_text));
+        )
+    }
+
+    test!(rm_1,
+b"<svg>
+    <rect fill='#ff0000' height='10' width='10' x='10' y='10'/>
+    <rect height='100' width='100'/>
+</svg>",
+"<svg>
+    <rect height='100' width='100'/>
+</svg>
+");
+
+    // the occluder is a sibling of the ancestor
+    test!(rm_2,
+b"<svg>
+    <g opacity='0.5'>
+        <circle cx='50' cy='50' r='10' stroke='#000000'/>
+    </g>
+    <g transform='scale(2)'>
+        <rect fill='#00ff00' height='50' width='50'/>
+    </g>
+</svg>",
+"<svg>
+    <g transform='scale(2)'>
+        <rect fill='#00ff00' height='50' width='50'/>
+    </g>
+</svg>
+");
+
+    // both elements are inside the same semi-transparent group
+    test!(rm_3,
+b"<svg>
+    <g opacity='0.5'>
+        <rect height='10' width='10' x='10' y='10'/>
+        <rect height='100' width='100'/>
+    </g>
+</svg>",
+"<svg>
+    <g opacity='0.5'>
+        <rect height='100' width='100'/>
+    </g>
+</svg>
+");
+
+    test!(rm_4,
+b"<svg>
+    <rect height='10' width='10' x='10' y='10'/>
+    <polygon points='0 0 200 0 0 200'/>
+</svg>",
+"<svg>
+    <polygon points='0 0 200 0 0 200'/>
+</svg>
+");
+
+    // the occluder is painted before the element
+    test_eq!(keep_1,
+b"<svg>
+    <rect height='100' width='100'/>
+    <rect fill='#ff0000' height='10' width='10' x='10' y='10'/>
+</svg>
+");
+
+    test_eq!(keep_2,
+b"<svg>
+    <rect height='10' width='10' x='10' y='10'/>
+    <rect fill-opacity='0.5' height='100' width='100'/>
+</svg>
+");
+
+    test_eq!(keep_3,
+b"<svg>
+    <rect height='10' width='10' x='10' y='10'/>
+    <g opacity='0.5'>
+        <rect height='100' width='100'/>
+    </g>
+</svg>
+");
+
+    // partially covered
+    test_eq!(keep_4,
+b"<svg>
+    <rect height='10' width='10' x='95' y='10'/>
+    <rect height='100' width='100'/>
+</svg>
+");
+
+    test_eq!(keep_5,
+b"<svg>
+    <rect height='10' width='10' x='10' y='10'/>
+    <rect height='100' transform='rotate(45)' width='100'/>
+</svg>
+");
+
+    test_eq!(keep_6,
+b"<svg>
+    <rect height='10' width='10' x='10' y='10'/>
+    <rect fill='none' height='100' width='100'/>
+</svg>
+");
+
+    // a concave polygon
+    test_eq!(keep_7,
+b"<svg>
+    <rect height='10' width='10' x='60' y='60'/>
+    <polygon points='0 0 100 0 100 50 50 50 50 100 0 100'/>
+</svg>
+");
+
+    test_eq!(keep_8,
+b"<svg xmlns:xlink='http://www.w3.org/1999/xlink'>
+    <rect id='rect1' height='10' width='10' x='10' y='10'/>
+    <rect height='100' width='100'/>
+    <use x='200' xlink:href='#rect1'/>
+</svg>
+");
+
+    test_eq!(keep_9,
+b"<svg>
+    <rect height='10' width='10' x='10' y='10'/>
+    <rect height='100' rx='5' width='100'/>
+</svg>
+");
+}
@@ -0,0 +1,270 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+use super::short::{EId, AId, Unit};
+
+use geom::{self, Rect};
+
+use svgdom::{Document, Node, AttributeValue, ValueId};
+
+// Removes elements which are entirely outside the root viewport.
+pub fn remove_offscreen_elements(doc: &Document) {
+    // doc must contain 'svg' node, so we can safely unwrap
+    let svg = doc.svg_element().unwrap();
+
+    let vp = match calc_viewport(&svg) {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut nodes = Vec::new();
+    collect_offscreen(&svg, &vp, &mut nodes);
+
+    for n in nodes {
+        n.remove();
+    }
+}
+
+// Calculates the visible area of the root 'svg' element in its user space.
+pub fn calc_viewport(svg: &Node) -> Option<Rect> {
+    let attrs = svg.attributes();
+
+    // the content outside the viewport can be rendered
+    match attrs.get_value(AId::Overflow) {
+        Some(&AttributeValue::PredefValue(ValueId::Visible)) |
+        Some(&AttributeValue::PredefValue(ValueId::Auto)) => return None,
+        _ => {}
+    }
+
+    let size = match (attrs.get_value(AId::Width), attrs.get_value(AId::Height)) {
+        (Some(&AttributeValue::Length(w)), Some(&AttributeValue::Length(h))) => {
+            if !(is_user_unit(w.unit) && is_user_unit(h.unit)) {
+                return None;
+            }
+            Some((w.num, h.num))
+        }
+        (None, None) => None,
+        _ => return None,
+    };
+
+    match attrs.get_value(AId::ViewBox) {
+        Some(&AttributeValue::NumberList(ref vb)) => {
+            if vb.len() != 4 {
+                return None;
+            }
+
+            // without the explicit size, the viewport has the 'viewBox' size
+            let (w, h) = size.unwrap_or((vb[2], vb[3]));
+
+            let aspect = match attrs.get_value(AId::PreserveAspectRatio) {
+                Some(&AttributeValue::String(ref s)) => Some(s.as_str()),
+                Some(_) => return None,
+                None => None,
+            };
+
+            let ts = try_opt!(::task::utils::view_box_transform(vb, aspect, w, h));
+
+            // the viewport rect can be bigger than the 'viewBox' when the aspect ratio
+            // is preserved, so we have to map it back to the user space
+            Some(Rect {
+                x1: -ts.e / ts.a,
+                y1: -ts.f / ts.d,
+                x2: (w - ts.e) / ts.a,
+                y2: (h - ts.f) / ts.d,
+            })
+        }
+        Some(_) => None,
+        None => size.map(|(w, h)| Rect::new(0.0, 0.0, w, h)),
+    }
+}
+
+fn is_user_unit(unit: Unit) -> bool {
+    unit == Unit::None || unit == Unit::Px
+}
+
+fn collect_offscreen(parent: &Node, vp: &Rect, nodes: &mut Vec<Node>) {
+    for node in parent.children().svg() {
+        // unwrap is safe, because we are iterating only over SVG elements
+        let is_group = match node.tag_id().unwrap() {
+            EId::G | EId::A => true,
+              EId::Switch
+            | EId::Path
+            | EId::Text
+            | EId::Image
+            | EId::Use => false,
+            _ if node.is_basic_shape() => false,
+            // nested 'svg' elements have their own viewport
+            _ => continue,
+        };
+
+        // filters can move the content to any position
+        if node.has_attribute(AId::Filter) {
+            continue;
+        }
+
+        if can_remove(&node) {
+            if let Some(Some(bbox)) = geom::calc_canvas_bbox(&node, true) {
+                if !bbox.intersects(vp) {
+                    nodes.push(node.clone());
+                    continue;
+                }
+            }
+        }
+
+        // 'switch' children should not be processed, because removing one of them
+        // can change the rendered one
+        if is_group {
+            collect_offscreen(&node, vp, nodes);
+        }
+    }
+}
+
+fn can_remove(node: &Node) -> bool {
+    // referenced elements can be rendered elsewhere
+    if node.descendants().svg().any(|n| n.is_used()) {
+        return false;
+    }
+
+    // the text bbox is only an approximation
+    !has_text(node)
+}
+
+// Checks that the element or elements referenced by its `use` children contain text.
+pub fn has_text(node: &Node) -> bool {
+    node.descendants().svg().any(|n| {
+        if n.is_tag_name(EId::Text) {
+            return true;
+        }
+
+        if n.is_tag_name(EId::Use) {
+            if let Some(AttributeValue::Link(link)) = n.attribute_value(AId::XlinkHref) {
+                return has_text(&link);
+            }
+        }
+
+        false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::{Document, WriteToString};
+
+    macro_rules! test {
+        ($name:ident, $in_text:expr, $out_text:expr) => (
+            base_test!($name, remove_offscreen_elements, $in_text, $out_text);
+        )
+    }
+
+    macro_rules! test_eq {
+        ($name:ident, $in_text:expr) => (
+            test!($name, $in_text, String::from_utf8_lossy($in_text));
+        )
+    }
+
+    test!(rm_1,
+b"<svg viewBox='0 0 100 100'>
+    <rect height='10' width='10' x='10'/>
+    <rect height='10' width='10' x='200'/>
+    <rect height='10' width='10' x='-10'/>
+</svg>",
+"<svg viewBox='0 0 100 100'>
+    <rect height='10' width='10' x='10'/>
+</svg>
+");
+
+    test!(rm_2,
+b"<svg height='100' width='100'>
+    <g>
+        <g transform='translate(200 0)'>
+            <rect height='10' width='10'/>
+            <rect height='10' width='10' x='20'/>
+        </g>
+        <rect height='10' width='10' transform='scale(-1 1)'/>
+        <rect height='10' width='10'/>
+    </g>
+</svg>",
+"<svg height='100' width='100'>
+    <g>
+        <rect height='10' width='10'/>
+    </g>
+</svg>
+");
+
+    // the viewport is wider than the 'viewBox', so it's from -50 to 150
+    test!(rm_3,
+b"<svg height='100' viewBox='0 0 100 100' width='200'>
+    <rect height='10' width='10' x='120'/>
+    <rect height='10' width='10' x='160'/>
+</svg>",
+"<svg height='100' viewBox='0 0 100 100' width='200'>
+    <rect height='10' width='10' x='120'/>
+</svg>
+");
+
+    test_eq!(keep_stroke_1,
+b"<svg viewBox='0 0 100 100'>
+    <rect height='10' stroke='#000000' stroke-width='4' width='10' x='101'/>
+</svg>
+");
+
+    test_eq!(keep_used_1,
+b"<svg viewBox='0 0 100 100' xmlns:xlink='http://www.w3.org/1999/xlink'>
+    <rect id='rect1' height='10' width='10' x='200'/>
+    <use x='-150' xlink:href='#rect1'/>
+</svg>
+");
+
+    test_eq!(keep_filter_1,
+b"<svg viewBox='0 0 100 100'>
+    <filter id='filter1'/>
+    <rect filter='url(#filter1)' height='10' width='10' x='200'/>
+</svg>
+");
+
+    test_eq!(keep_nested_svg_1,
+b"<svg viewBox='0 0 100 100'>
+    <svg overflow='visible'>
+        <rect height='10' width='10' x='200'/>
+    </svg>
+</svg>
+");
+
+    test_eq!(keep_text_1,
+b"<svg viewBox='0 0 100 100'>
+    <text x='200'>Text</text>
+</svg>
+");
+
+    test_eq!(keep_no_viewport_1,
+b"<svg>
+    <rect height='10' width='10' x='200'/>
+</svg>
+");
+
+    test_eq!(keep_overflow_1,
+b"<svg overflow='visible' viewBox='0 0 100 100'>
+    <rect height='10' width='10' x='200'/>
+</svg>
+");
+}
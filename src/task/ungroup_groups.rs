@@ -21,38 +21,105 @@
 ****************************************************************************/
 
 use super::short::{EId, AId};
+use super::apply_transforms::utils as ts_utils;
+use super::paths;
 
-use svgdom::{Document, Node, AttributeValue};
+use svgdom::{Document, Node, AttributeType, AttributeValue, ValueId, WriteOptions, WriteBuffer};
+use svgdom::types::Transform;
+use svgdom::types::path::Path;
 
-pub fn ungroup_groups(doc: &Document) {
+use options::Options;
+
+pub fn ungroup_groups(doc: &Document, options: &Options, opt: &WriteOptions) {
     let mut groups = Vec::with_capacity(16);
 
     // doc must contain 'svg' node, so we can safely unwrap
     let svg = doc.svg_element().unwrap();
     loop {
-        _ungroup_groups(&svg, &mut groups);
+        _ungroup_groups(&svg, options, opt, &mut groups);
 
         if groups.is_empty() {
             break;
         }
 
         while let Some(g) = groups.pop() {
-            ungroup_group(&g);
+            ungroup_group(&g, options, opt);
             g.remove();
         }
     }
+
+    ungroup_root_group(doc);
+}
+
+// Ungroup a group, which is the only child of the root 'svg' element.
+//
+// Inheritable attributes are moved to the 'svg' element, which is possible only
+// when there are no other elements that can inherit them.
+fn ungroup_root_group(doc: &Document) {
+    // doc must contain 'svg' node, so we can safely unwrap
+    let svg = doc.svg_element().unwrap();
+
+    let mut g = None;
+    for child in svg.children().svg() {
+        if child.is_tag_name(EId::G) && g.is_none() {
+            g = Some(child);
+        } else if child.is_tag_name(EId::Defs) && !child.has_children() {
+            // empty 'defs' will be removed later
+        } else {
+            return;
+        }
+    }
+
+    let g = match g {
+        Some(g) => g,
+        None => return,
+    };
+
+    if g.is_used() {
+        return;
+    }
+
+    {
+        let attrs = g.attributes();
+        let is_valid = attrs.iter_svg().all(|(aid, attr)| {
+            aid == AId::Id || (attr.is_presentation() && attr.is_inheritable())
+        });
+
+        if !is_valid {
+            return;
+        }
+    }
+
+    for (aid, attr) in g.attributes().iter_svg() {
+        if aid == AId::Id {
+            continue;
+        }
+
+        match attr.value {
+            AttributeValue::Link(ref iri) | AttributeValue::FuncLink(ref iri) => {
+                // if it's fail - it's already a huge problem, so unwrap is harmless
+                svg.set_link_attribute(aid, iri.clone()).unwrap();
+            }
+            _ => svg.set_attribute(aid, attr.value.clone()),
+        }
+    }
+
+    while g.has_children() {
+        let c = g.first_child().unwrap();
+        c.detach();
+        g.insert_before(&c);
+    }
+
+    g.remove();
 }
 
 // Fill 'groups' vec with 'g' elements that should be removed.
 // This method is recursive.
-fn _ungroup_groups(root: &Node, groups: &mut Vec<Node>) {
+fn _ungroup_groups(root: &Node, options: &Options, opt: &WriteOptions, groups: &mut Vec<Node>) {
     // We can't ungoup groups if they have one of the listed attribute.
     // Checkout 'painting-marker-02-f.svg' in 'W3C_SVG_11_TestSuite' for details.
     let invalid_attrs = [AId::Mask, AId::ClipPath, AId::Filter];
 
-    // TODO: we should not ungroup groups with non-inheritable attributes.
-    // TODO: ungroup groups with 'transform' attribute when all/most of children has it too
-
     for node in root.children() {
         if node.is_tag_name(EId::G) {
             if !node.has_children() && !node.has_attribute(AId::Filter) {
@@ -72,6 +139,12 @@ fn _ungroup_groups(root: &Node, groups: &mut Vec<Node>) {
                 continue;
             }
 
+            // Non-inheritable attributes are applied to the group as a whole,
+            // so we can't simply copy them to the children.
+            if has_non_inheritable_attrs(&node) {
+                continue;
+            }
+
             // The 'clipPath' doesn't support 'g' elements, but some editors still insert them.
             // Correct render application will skip a 'g' element and all it's children,
             // so if we ungroup this group we will actually enable clipping.
@@ -103,15 +176,121 @@ fn _ungroup_groups(root: &Node, groups: &mut Vec<Node>) {
                 groups.push(node.clone());
                 continue;
             }
+
+            if is_transform_group(&node) && is_transform_ungroup_shorter(&node, options, opt) {
+                // group with only 'transform' attribute, which can be moved to children
+                groups.push(node.clone());
+                continue;
+            }
         }
 
         if node.has_children() {
-            _ungroup_groups(&node, groups);
+            _ungroup_groups(&node, options, opt, groups);
         }
     }
 }
 
-fn ungroup_group(g: &Node) {
+// 'opacity' and 'display' are non-inheritable too, but they can be moved to a single child.
+// See 'ungroup_group' for details.
+fn has_non_inheritable_attrs(g: &Node) -> bool {
+    let attrs = g.attributes();
+    attrs.iter_svg().any(|(aid, attr)| {
+           attr.is_presentation()
+        && !attr.is_inheritable()
+        && aid != AId::Opacity
+        && aid != AId::Display
+    })
+}
+
+fn is_transform_group(g: &Node) -> bool {
+    {
+        let attrs = g.attributes();
+        if attrs.iter().count() != 1 || !attrs.contains(AId::Transform) {
+            return false;
+        }
+    }
+
+    for child in g.children().svg() {
+        // Referenced elements are rendered without parent's transform,
+        // so we can't change their transform.
+        if child.is_used() {
+            return false;
+        }
+
+        // clip paths in user space are too complex to check
+        if let Some(AttributeValue::FuncLink(link)) = child.attribute_value(AId::ClipPath) {
+            if !link.has_attribute_with_value(AId::ClipPathUnits, ValueId::ObjectBoundingBox) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+// Checks that moving the group's transform to the children
+// will produce a smaller output than the group itself.
+fn is_transform_ungroup_shorter(g: &Node, options: &Options, opt: &WriteOptions) -> bool {
+    let ts = ts_utils::get_ts(g);
+    let ts_len = transform_len(&ts, opt) as isize;
+
+    // <g transform=""></g>
+    let group_len = 20 + ts_len;
+
+    let mut len = 0;
+    for child in g.children().svg() {
+        if ts_utils::can_bake_transform(&child, &ts) {
+            if let Some(AttributeValue::Path(path)) = child.attribute_value(AId::D) {
+                let old_len = path_len(&path, opt) as isize;
+
+                let mut new_path = path;
+                new_path.conv_to_absolute();
+                ts_utils::transform_path(&mut new_path, &ts);
+                process_baked_path(&mut new_path, options, opt);
+
+                len += path_len(&new_path, opt) as isize - old_len;
+            }
+
+            continue;
+        }
+
+        if child.has_attribute(AId::Transform) {
+            let child_ts = ts_utils::get_ts(&child);
+            let mut new_ts = ts;
+            new_ts.append(&child_ts);
+
+            len += transform_len(&new_ts, opt) as isize - transform_len(&child_ts, opt) as isize;
+        } else {
+            // transform=""
+            len += 13 + ts_len;
+        }
+    }
+
+    len < group_len
+}
+
+fn transform_len(ts: &Transform, opt: &WriteOptions) -> usize {
+    let mut buf = Vec::new();
+    AttributeValue::Transform(*ts).write_buf_opt(opt, &mut buf);
+    buf.len()
+}
+
+fn path_len(path: &Path, opt: &WriteOptions) -> usize {
+    let mut buf = Vec::new();
+    AttributeValue::Path(path.clone()).write_buf_opt(opt, &mut buf);
+    buf.len()
+}
+
+// Paths are already processed at this point, but baking makes them absolute,
+// so we have to process them again.
+fn process_baked_path(path: &mut Path, options: &Options, opt: &WriteOptions) {
+    if options.paths_to_relative {
+        // paths with markers can't be baked
+        paths::process_path(path, false, options, opt.paths.coordinates_precision);
+    }
+}
+
+fn ungroup_group(g: &Node, options: &Options, opt: &WriteOptions) {
     for (aid, attr) in g.attributes().iter_svg() {
         for child in g.children() {
             if aid == AId::Opacity {
@@ -127,6 +306,21 @@ fn ungroup_group(g: &Node) {
             }
 
             if aid == AId::Transform {
+                let ts = *attr.value.as_transform().unwrap();
+                if ts_utils::can_bake_transform(&child, &ts) {
+                    ts_utils::bake_transform(&child, &ts);
+
+                    {
+                        let mut attrs = child.attributes_mut();
+                        if let Some(&mut AttributeValue::Path(ref mut path))
+                            = attrs.get_value_mut(AId::D) {
+                            process_baked_path(path, options, opt);
+                        }
+                    }
+
+                    continue;
+                }
+
                 if child.has_attribute(aid) {
                     // we should multiply transform matrices
                     let mut t1 = *attr.value.as_transform().unwrap();
@@ -171,6 +365,7 @@ mod tests {
     use super::*;
     use svgdom::{Document, WriteToString};
     use task::{group_defs, remove_empty_defs, rm_unused_defs};
+    use options::Options;
 
     macro_rules! test {
         ($name:ident, $in_text:expr, $out_text:expr) => (
@@ -182,7 +377,7 @@ mod tests {
                 group_defs(&doc);
 
                 // actual test
-                ungroup_groups(&doc);
+                ungroup_groups(&doc, &Options::default(), &write_opt_for_tests!());
 
                 // We must check that we moved linked elements correctly.
                 // If we not, than referenced elements will be removed. Which is wrong.
@@ -311,6 +506,7 @@ b"<svg>
         <rect/>
         <rect/>
     </g>
+    <rect/>
 </svg>
 ");
 
@@ -457,6 +653,7 @@ b"<svg>
 </svg>
 ");
 
+    // moving the transform to the children is shorter than the group
     test!(ungroup_with_transform_4,
 b"<svg>
     <g transform='translate(10 20)'>
@@ -471,8 +668,273 @@ b"<svg>
     </g>
 </svg>",
 "<svg>
+    <rect transform='translate(30 50)'/>
+    <rect transform='translate(10 20)'/>
+</svg>
+");
+
+    test!(ungroup_with_transform_5,
+b"<svg>
     <g transform='translate(10 20)'>
-        <rect transform='translate(20 30)'/>
+        <rect transform='translate(1 2)'/>
+        <rect transform='translate(3 4)'/>
+    </g>
+</svg>",
+"<svg>
+    <rect transform='translate(11 22)'/>
+    <rect transform='translate(13 24)'/>
+</svg>
+");
+
+    // apply transform to path data
+    test!(ungroup_with_transform_6,
+b"<svg>
+    <g transform='translate(10 20)'>
+        <path d='M 10 10 L 20 20'/>
+        <path d='M 0 0 L 5 5'/>
+    </g>
+</svg>",
+"<svg>
+    <path d='M 20 30 L 30 40'/>
+    <path d='M 10 20 L 15 25'/>
+</svg>
+");
+
+    test!(ungroup_with_transform_7,
+b"<svg>
+    <g transform='scale(2)'>
+        <path d='M 10 10 L 20 20'/>
+    </g>
+</svg>",
+"<svg>
+    <path d='M 20 20 L 40 40' stroke-width='2'/>
+</svg>
+");
+
+    // baked paths should be processed again
+    #[test]
+    fn ungroup_with_transform_8() {
+        let doc = Document::from_data(b"<svg>
+    <g transform='rotate(90)'>
+        <path d='m 10 10 h 10 v 10'/>
+    </g>
+</svg>").unwrap();
+
+        let mut options = Options::default();
+        options.paths_to_relative = true;
+        options.convert_segments = true;
+        ungroup_groups(&doc, &options, &write_opt_for_tests!());
+
+        assert_eq_text!(doc.to_string_with_opt(&write_opt_for_tests!()),
+"<svg>
+    <path d='m -10 10 v 10 h -10'/>
+</svg>
+");
+    }
+
+    // group is shorter
+    test_eq!(skip_ungroup_with_transform_1,
+b"<svg>
+    <g transform='translate(10 20)'>
+        <rect/>
+        <rect/>
+        <rect/>
+    </g>
+</svg>
+");
+
+    // referenced elements can't be changed
+    test_eq!(skip_ungroup_with_transform_2,
+b"<svg>
+    <g transform='translate(10 20)'>
+        <rect id='r1' transform='translate(1 2)'/>
+        <rect transform='translate(3 4)'/>
+    </g>
+    <use xlink:href='#r1'/>
+</svg>
+");
+
+    // baked paths are longer than the group
+    test_eq!(skip_ungroup_with_transform_3,
+b"<svg>
+    <g transform='translate(0.123456 0.654321)'>
+        <path d='M 0 0 L 1 1'/>
+        <path d='M 0 0 L 1 1'/>
+        <path d='M 0 0 L 1 1'/>
+    </g>
+</svg>
+");
+
+    // Non-inheritable attributes matrix.
+    // Group can be removed only when attribute can be moved to a single child.
+
+    test!(non_inheritable_opacity_1,
+b"<svg>
+    <g opacity='0.5'>
+        <rect opacity='0.5'/>
+    </g>
+</svg>",
+"<svg>
+    <rect opacity='0.25'/>
+</svg>
+");
+
+    // children can overlap, so opacity must be applied to the whole group
+    test_eq!(non_inheritable_opacity_2,
+b"<svg>
+    <g opacity='0.5'>
+        <rect/>
+        <rect/>
+    </g>
+</svg>
+");
+
+    test_eq!(non_inheritable_filter_1,
+b"<svg>
+    <defs>
+        <filter id='f1'/>
+    </defs>
+    <g filter='url(#f1)'>
+        <rect/>
+    </g>
+</svg>
+");
+
+    test_eq!(non_inheritable_filter_2,
+b"<svg>
+    <defs>
+        <filter id='f1'/>
+    </defs>
+    <g filter='url(#f1)'>
+        <rect/>
+        <rect/>
+    </g>
+</svg>
+");
+
+    test_eq!(non_inheritable_mask_1,
+b"<svg>
+    <defs>
+        <mask id='m1'/>
+    </defs>
+    <g mask='url(#m1)'>
+        <rect/>
+    </g>
+</svg>
+");
+
+    test_eq!(non_inheritable_mask_2,
+b"<svg>
+    <defs>
+        <mask id='m1'/>
+    </defs>
+    <g mask='url(#m1)'>
+        <rect/>
+        <rect/>
+    </g>
+</svg>
+");
+
+    test_eq!(non_inheritable_clip_path_1,
+b"<svg>
+    <defs>
+        <clipPath id='cp1'/>
+    </defs>
+    <g clip-path='url(#cp1)'>
+        <rect/>
+    </g>
+</svg>
+");
+
+    test_eq!(non_inheritable_clip_path_2,
+b"<svg>
+    <defs>
+        <clipPath id='cp1'/>
+    </defs>
+    <g clip-path='url(#cp1)'>
+        <rect/>
+        <rect/>
+    </g>
+</svg>
+");
+
+    test!(non_inheritable_transform_1,
+b"<svg>
+    <g transform='translate(10 20)'>
+        <rect transform='scale(2)'/>
+    </g>
+</svg>",
+"<svg>
+    <rect transform='matrix(2 0 0 2 10 20)'/>
+</svg>
+");
+
+    test_eq!(non_inheritable_transform_2,
+b"<svg>
+    <g transform='translate(10 20)'>
+        <rect/>
+        <rect/>
+        <rect/>
+    </g>
+</svg>
+");
+
+    test_eq!(non_inheritable_enable_background_1,
+b"<svg>
+    <g enable-background='new'>
+        <rect/>
+    </g>
+</svg>
+");
+
+    test!(ungroup_root_group_1,
+b"<svg>
+    <g fill='#ff0000' stroke='#00ff00'>
+        <rect/>
+        <rect/>
+    </g>
+</svg>",
+"<svg fill='#ff0000' stroke='#00ff00'>
+    <rect/>
+    <rect/>
+</svg>
+");
+
+    test!(ungroup_root_group_2,
+b"<svg fill='#0000ff'>
+    <defs/>
+    <g fill='#ff0000'>
+        <rect/>
+        <rect/>
+    </g>
+</svg>",
+"<svg fill='#ff0000'>
+    <rect/>
+    <rect/>
+</svg>
+");
+
+    // defs content can inherit attributes from the root element
+    test_eq!(skip_ungroup_root_group_1,
+b"<svg>
+    <defs>
+        <clipPath id='cp1'>
+            <rect/>
+        </clipPath>
+    </defs>
+    <g fill='#ff0000'>
+        <rect clip-path='url(#cp1)'/>
+        <rect/>
+    </g>
+</svg>
+");
+
+    // 'svg' doesn't support the 'transform' attribute
+    test_eq!(skip_ungroup_root_group_2,
+b"<svg>
+    <g fill='#ff0000' transform='translate(10 20)'>
+        <rect/>
+        <rect/>
         <rect/>
     </g>
 </svg>
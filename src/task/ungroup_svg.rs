@@ -0,0 +1,355 @@
+/****************************************************************************
+**
+** svgcleaner could help you to clean up your SVG files
+** from unnecessary data.
+** Copyright (C) 2012-2017 Evgeniy Reizner
+**
+** This program is free software; you can redistribute it and/or modify
+** it under the terms of the GNU General Public License as published by
+** the Free Software Foundation; either version 2 of the License, or
+** (at your option) any later version.
+**
+** This program is distributed in the hope that it will be useful,
+** but WITHOUT ANY WARRANTY; without even the implied warranty of
+** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+** GNU General Public License for more details.
+**
+** You should have received a copy of the GNU General Public License along
+** with this program; if not, write to the Free Software Foundation, Inc.,
+** 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+**
+****************************************************************************/
+
+use super::short::{EId, AId, Unit};
+
+use geom::{self, Rect};
+
+use svgdom::{Document, Node, AttributeType, AttributeValue, ValueId};
+use svgdom::types::{Length, Transform};
+
+// Converts nested 'svg' elements into 'g' elements.
+//
+// A nested 'svg' establishes a new viewport, which can be expressed via a translate/scale
+// transform and an optional clip path.
+pub fn ungroup_nested_svg(doc: &Document) {
+    // doc must contain 'svg' node, so we can safely unwrap
+    let root = doc.svg_element().unwrap();
+
+    let nodes: Vec<Node> = doc.descendants().svg()
+        .filter(|n| n.is_tag_name(EId::Svg) && *n != root)
+        .collect();
+
+    // process the deepest elements first
+    for node in nodes.iter().rev() {
+        if let Some(vp) = prepare_viewport(node) {
+            conv_to_group(doc, node, &vp);
+        }
+    }
+}
+
+pub struct Viewport {
+    // 'svg' element's content transform
+    pub ts: Transform,
+    // viewport rect in the content coordinates, if clipping is needed
+    pub clip: Option<Rect>,
+}
+
+fn prepare_viewport(node: &Node) -> Option<Viewport> {
+    // TODO: replace links to the 'svg' with links to the 'g'
+    if node.is_used() {
+        return None;
+    }
+
+    // 'svg' doesn't support 'transform' attribute
+    if node.has_attribute(AId::Transform) {
+        return None;
+    }
+
+    let attrs = node.attributes();
+
+    let x = get_value!(attrs, Length, AId::X, Length::zero());
+    let y = get_value!(attrs, Length, AId::Y, Length::zero());
+    // 'width' and 'height' are '100%' by default
+    let w = get_value!(attrs, Length, AId::Width, Length::new(100.0, Unit::Percent));
+    let h = get_value!(attrs, Length, AId::Height, Length::new(100.0, Unit::Percent));
+
+    calc_viewport(node, x, y, w, h)
+}
+
+// Calculates a viewport of the 'svg' or 'symbol' element.
+//
+// 'x', 'y', 'width' and 'height' are passed separately, because in case of 'symbol'
+// they are defined by the 'use' element.
+pub fn calc_viewport(node: &Node, x: Length, y: Length, w: Length, h: Length)
+                     -> Option<Viewport> {
+    if !(x.unit == Unit::None && y.unit == Unit::None
+         && w.unit == Unit::None && h.unit == Unit::None) {
+        return None;
+    }
+
+    // zero-sized viewport disables rendering, which is processed by other methods
+    if !(w.num > 0.0 && h.num > 0.0) {
+        return None;
+    }
+
+    // percentage values are resolved relative to the nearest viewport
+    // which we are going to remove
+    if node.descendants().svg().any(|n| n != *node && has_percent_values(&n)) {
+        return None;
+    }
+
+    let attrs = node.attributes();
+
+    let mut ts = match attrs.get_value(AId::ViewBox) {
+        Some(&AttributeValue::NumberList(ref vb)) => {
+            let aspect = match attrs.get_value(AId::PreserveAspectRatio) {
+                Some(&AttributeValue::String(ref s)) => Some(s.as_str()),
+                Some(_) => return None,
+                None => None,
+            };
+
+            try_opt!(::task::utils::view_box_transform(vb, aspect, w.num, h.num))
+        }
+        Some(_) => return None,
+        None => Transform::default(),
+    };
+    ts.e += x.num;
+    ts.f += y.num;
+
+    // The 'hidden' value is set by the user agent style sheet for nested 'svg' elements.
+    let is_hidden = match attrs.get_value(AId::Overflow) {
+        Some(&AttributeValue::PredefValue(id)) => {
+            !(id == ValueId::Visible || id == ValueId::Auto)
+        }
+        _ => true,
+    };
+
+    let mut clip = None;
+    if is_hidden {
+        // viewport rect in the content coordinates
+        let vp_rect = Rect {
+            x1: (x.num - ts.e) / ts.a,
+            y1: (y.num - ts.f) / ts.d,
+            x2: (x.num + w.num - ts.e) / ts.a,
+            y2: (y.num + h.num - ts.f) / ts.d,
+        };
+
+        let is_overflow = match geom::calc_bbox(node, true) {
+            Some(Some(bbox)) => !vp_rect.contains(&bbox),
+            // empty content doesn't overflow anything
+            Some(None) => false,
+            None => true,
+        };
+
+        if is_overflow {
+            clip = Some(vp_rect);
+        }
+    }
+
+    // The element's clip path, mask and filter are resolved in the parent user space,
+    // which will be changed by the new transform and clip path.
+    if !ts.is_default() || clip.is_some() {
+        if node.has_attributes(&[AId::ClipPath, AId::Mask, AId::Filter]) {
+            return None;
+        }
+    }
+
+    Some(Viewport {
+        ts: ts,
+        clip: clip,
+    })
+}
+
+pub fn has_percent_values(node: &Node) -> bool {
+    let attrs = node.attributes();
+    attrs.iter().any(|a| {
+        match a.value {
+            AttributeValue::Length(ref l) => l.unit == Unit::Percent,
+            AttributeValue::LengthList(ref list) => list.iter().any(|l| l.unit == Unit::Percent),
+            _ => false,
+        }
+    })
+}
+
+pub fn conv_to_group(doc: &Document, node: &Node, vp: &Viewport) {
+    node.set_tag_name(EId::G);
+
+    // remove viewport attributes and all other non-presentation attributes,
+    // which are not supported by the 'g' element
+    let rm_list: Vec<AId> = node.attributes().iter_svg()
+        .filter(|&(aid, attr)| !attr.is_presentation() && aid != AId::Id)
+        .map(|(aid, _)| aid)
+        .collect();
+    node.remove_attributes(&rm_list);
+    // 'overflow' is a presentation attribute, but 'g' doesn't support it
+    node.remove_attribute(AId::Overflow);
+
+    if !vp.ts.is_default() {
+        node.set_attribute(AId::Transform, vp.ts);
+    }
+
+    if let Some(ref r) = vp.clip {
+        let clip_path = doc.create_element(EId::ClipPath);
+        clip_path.set_id(::task::utils::gen_id(doc, "clipPath"));
+
+        let rect = doc.create_element(EId::Rect);
+        rect.set_attribute(AId::X, r.x1);
+        rect.set_attribute(AId::Y, r.y1);
+        rect.set_attribute(AId::Width, r.x2 - r.x1);
+        rect.set_attribute(AId::Height, r.y2 - r.y1);
+        clip_path.append(&rect);
+
+        ::task::utils::get_defs(doc).append(&clip_path);
+
+        // if it's fail - it's already a huge problem, so unwrap is harmless
+        node.set_link_attribute(AId::ClipPath, clip_path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svgdom::{Document, WriteToString};
+
+    macro_rules! test {
+        ($name:ident, $in_text:expr, $out_text:expr) => (
+            base_test!($name, ungroup_nested_svg, $in_text, $out_text);
+        )
+    }
+
+    macro_rules! test_eq {
+        ($name:ident, $in_text:expr) => (
+            test!($name, $in_text, String::from_utf8_lossy($in_text));
+        )
+    }
+
+    test!(conv_1,
+b"<svg>
+    <svg height='50' overflow='visible' width='100' x='10' y='20'>
+        <rect/>
+    </svg>
+</svg>",
+"<svg>
+    <g transform='translate(10 20)'>
+        <rect/>
+    </g>
+</svg>
+");
+
+    test!(conv_2,
+b"<svg>
+    <svg height='100' viewBox='0 0 50 50' width='100'>
+        <rect height='10' width='10'/>
+    </svg>
+</svg>",
+"<svg>
+    <g transform='scale(2)'>
+        <rect height='10' width='10'/>
+    </g>
+</svg>
+");
+
+    // 'xMidYMid meet' by default
+    test!(conv_3,
+b"<svg>
+    <svg height='50' viewBox='0 0 10 10' width='100'>
+        <rect height='10' width='10'/>
+    </svg>
+</svg>",
+"<svg>
+    <g transform='matrix(5 0 0 5 25 0)'>
+        <rect height='10' width='10'/>
+    </g>
+</svg>
+");
+
+    test!(conv_4,
+b"<svg>
+    <svg height='50' preserveAspectRatio='none' viewBox='0 0 10 10' width='100'>
+        <rect height='10' width='10'/>
+    </svg>
+</svg>",
+"<svg>
+    <g transform='scale(10 5)'>
+        <rect height='10' width='10'/>
+    </g>
+</svg>
+");
+
+    // content overflows the viewport
+    test!(conv_with_clip_1,
+b"<svg>
+    <svg height='10' width='10'>
+        <rect height='20' width='20'/>
+    </svg>
+</svg>",
+"<svg>
+    <defs>
+        <clipPath id='clipPath1'>
+            <rect height='10' width='10' x='0' y='0'/>
+        </clipPath>
+    </defs>
+    <g clip-path='url(#clipPath1)'>
+        <rect height='20' width='20'/>
+    </g>
+</svg>
+");
+
+    // stroke overflows the viewport
+    test!(conv_with_clip_2,
+b"<svg>
+    <svg height='10' width='10'>
+        <rect height='10' stroke='#000000' width='10'/>
+    </svg>
+</svg>",
+"<svg>
+    <defs>
+        <clipPath id='clipPath1'>
+            <rect height='10' width='10' x='0' y='0'/>
+        </clipPath>
+    </defs>
+    <g clip-path='url(#clipPath1)'>
+        <rect height='10' stroke='#000000' width='10'/>
+    </g>
+</svg>
+");
+
+    test_eq!(keep_1,
+b"<svg>
+    <svg height='50%' width='10'>
+        <rect/>
+    </svg>
+</svg>
+");
+
+    test_eq!(keep_2,
+b"<svg>
+    <svg height='10' width='10'>
+        <rect width='50%'/>
+    </svg>
+</svg>
+");
+
+    test_eq!(keep_3,
+b"<svg>
+    <defs>
+        <svg id='svg1' height='10' width='10'/>
+    </defs>
+    <use xlink:href='#svg1'/>
+</svg>
+");
+
+    // the clip path is defined in the parent user space
+    test_eq!(keep_4,
+b"<svg>
+    <defs>
+        <clipPath id='clipPath1'>
+            <rect height='5' width='5'/>
+        </clipPath>
+    </defs>
+    <svg clip-path='url(#clipPath1)' height='10' overflow='visible' width='10' x='10'>
+        <rect/>
+    </svg>
+</svg>
+");
+}